@@ -0,0 +1,22 @@
+-- Milk bucket shared by every replica when MILK_RATE_LIMITER=postgres
+CREATE TABLE IF NOT EXISTS milk_buckets
+(
+    name         TEXT PRIMARY KEY,
+    tokens       BIGINT      NOT NULL,
+    capacity     BIGINT      NOT NULL,
+    per_interval BIGINT      NOT NULL,
+    interval_ms  BIGINT      NOT NULL,
+    refilled_at  TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Whole refill intervals elapsed since the bucket was last brought up to date
+CREATE OR REPLACE FUNCTION milk_bucket_ticks(refilled_at TIMESTAMPTZ, interval_ms BIGINT) RETURNS BIGINT AS
+$$
+SELECT GREATEST(0, FLOOR(EXTRACT(EPOCH FROM now() - refilled_at) * 1000 / interval_ms))::BIGINT
+$$ LANGUAGE SQL STABLE;
+
+-- `refilled_at` moved forward by those whole intervals, keeping the partial one pending
+CREATE OR REPLACE FUNCTION milk_bucket_refilled_at(refilled_at TIMESTAMPTZ, interval_ms BIGINT) RETURNS TIMESTAMPTZ AS
+$$
+SELECT refilled_at + milk_bucket_ticks(refilled_at, interval_ms) * interval_ms * INTERVAL '1 millisecond'
+$$ LANGUAGE SQL STABLE;
@@ -0,0 +1,33 @@
+-- Day 12 games, saved after every change so they outlive the process
+CREATE TABLE IF NOT EXISTS connect_games
+(
+    id         UUID PRIMARY KEY,
+    width      INT         NOT NULL,
+    height     INT         NOT NULL,
+    connect    INT         NOT NULL,
+    -- rows from top to bottom, `c` for cookie, `m` for milk and `.` for empty cells
+    grid       TEXT        NOT NULL,
+    randomized BOOLEAN     NOT NULL,
+    -- `cookie`, `milk` or `draw` once the game is over
+    outcome    TEXT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS connect_moves
+(
+    game_id UUID NOT NULL REFERENCES connect_games (id) ON DELETE CASCADE,
+    ply     INT  NOT NULL,
+    team    TEXT NOT NULL,
+    -- from 1, as in the routes
+    col     INT  NOT NULL,
+    PRIMARY KEY (game_id, ply)
+);
+
+-- Who holds each team of a game, with a NULL player for the computer
+CREATE TABLE IF NOT EXISTS connect_seats
+(
+    game_id UUID NOT NULL REFERENCES connect_games (id) ON DELETE CASCADE,
+    team    TEXT NOT NULL,
+    player  TEXT,
+    PRIMARY KEY (game_id, team)
+);
@@ -0,0 +1,13 @@
+-- Day 12 results, added when a move ends a game and never changed, so that resetting, undoing
+-- or deleting games leaves the statistics alone
+CREATE TABLE IF NOT EXISTS connect_results
+(
+    id          BIGINT GENERATED ALWAYS AS IDENTITY PRIMARY KEY,
+    game_id     UUID        NOT NULL,
+    -- `cookie`, `milk` or `draw`
+    outcome     TEXT        NOT NULL,
+    -- handles of the players holding each team, NULL for the computer
+    cookie      TEXT,
+    milk        TEXT,
+    finished_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -1,12 +1,27 @@
+use futures_util::{SinkExt, StreamExt};
+use poem::http::header::{AUTHORIZATION, LOCATION};
 use poem::http::StatusCode;
-use poem::middleware::AddData;
-use poem::web::{Data, Path};
-use poem::{get, handler, post, Endpoint, EndpointExt, IntoResponse, Route};
+use poem::middleware::{AddData, CookieJarManager};
+use poem::web::cookie::Cookie;
+use poem::web::websocket::{Message, WebSocket, WebSocketStream};
+use poem::web::{Accept, Data, Json, Path, Query};
+use poem::{
+    delete, get, handler, post, Endpoint, EndpointExt, FromRequest, IntoResponse, Request,
+    RequestBody, Response, Route,
+};
+use rand::seq::SliceRandom;
 use rand::{Rng, SeedableRng};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use sqlx::types::uuid::Builder;
+use sqlx::types::Uuid;
+use std::collections::HashMap;
+use std::ops::{BitAnd, BitOr, Not};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, Notify};
 
-#[derive(Clone, Copy, Deserialize, Debug, PartialEq)]
+#[derive(Clone, Copy, Deserialize, Serialize, Debug, PartialEq)]
 enum Token {
     #[serde(rename = "cookie")]
     Cookie,
@@ -15,6 +30,33 @@ enum Token {
     Empty,
 }
 
+impl Token {
+    fn opponent(self) -> Token {
+        match self {
+            Token::Cookie => Token::Milk,
+            Token::Milk => Token::Cookie,
+            Token::Empty => Token::Empty,
+        }
+    }
+
+    /// The name stored in the database, which is also `draw` for a full board.
+    fn name(self) -> &'static str {
+        match self {
+            Token::Cookie => "cookie",
+            Token::Milk => "milk",
+            Token::Empty => "draw",
+        }
+    }
+
+    fn from_name(name: &str) -> Token {
+        match name {
+            "cookie" => Token::Cookie,
+            "milk" => Token::Milk,
+            _ => Token::Empty,
+        }
+    }
+}
+
 impl From<Token> for char {
     fn from(value: Token) -> Self {
         match value {
@@ -25,26 +67,289 @@ impl From<Token> for char {
     }
 }
 
+/// Largest width or height accepted for a board.
+const MAX_SIDE: usize = 16;
+
+#[derive(Clone)]
 struct BoardImpl {
-    inner: [[Token; 4]; 4],
+    width: usize,
+    height: usize,
+    connect: usize,
+    // rows from top to bottom, changed through `set` to keep `bits` in step
+    inner: Vec<Vec<Token>>,
+    // `inner` again for fast win checks
+    bits: Bitboard,
+    // every token placed since the last reset, unless `randomized` replaced the grid
+    moves: Vec<Move>,
+    randomized: bool,
     rng: rand::rngs::StdRng,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Move {
+    team: Token,
+    // from 0, unlike the columns in routes and the notation
+    column: usize,
+}
+
+#[derive(Clone, Copy, Deserialize, Debug)]
+struct BoardSize {
+    width: usize,
+    height: usize,
+    connect: usize,
+}
+
+impl Default for BoardSize {
+    fn default() -> Self {
+        Self {
+            width: 4,
+            height: 4,
+            connect: 4,
+        }
+    }
+}
+
+impl BoardSize {
+    fn validate(self) -> Result<Self, String> {
+        if !(1..=MAX_SIDE).contains(&self.width) || !(1..=MAX_SIDE).contains(&self.height) {
+            Err(format!("Width and height must be between 1 and {MAX_SIDE}"))
+        } else if !(2..=self.width.max(self.height)).contains(&self.connect) {
+            Err("Connect must be between 2 and the longest side".to_string())
+        } else {
+            Ok(self)
+        }
+    }
+}
+
+// Directions scanned for a winning line, as (row, column) steps. The order decides which team is
+// reported when a filled board has lines for both.
+const DIRECTIONS: [(isize, isize); 4] = [(1, 1), (-1, 1), (0, 1), (1, 0)];
+
+/// Words in `Bits`, enough for the largest board with its spare column.
+const WORDS: usize = (MAX_SIDE * (MAX_SIDE + 1)).div_ceil(u128::BITS as usize);
+
+/// A set of board cells, one bit each, from bit 0 of the first word upwards.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Bits([u128; WORDS]);
+
+impl Bits {
+    fn bit(index: usize) -> Self {
+        let mut bits = Self::default();
+        bits.0[index / 128] = 1 << (index % 128);
+        bits
+    }
+
+    fn is_empty(self) -> bool {
+        self == Self::default()
+    }
+
+    fn count_ones(self) -> u32 {
+        self.0.iter().map(|word| word.count_ones()).sum()
+    }
+
+    fn trailing_zeros(self) -> u32 {
+        let mut zeros = 0;
+        for word in self.0 {
+            zeros += word.trailing_zeros();
+            if word != 0 {
+                break;
+            }
+        }
+        zeros
+    }
+
+    /// Moves every bit `by` places towards bit 0, or away from it when `by` is negative, so that
+    /// bit `i` of the result is bit `i + by` of `self`.
+    fn shift(self, by: isize) -> Self {
+        let (words, amount) = (by.div_euclid(128), by.rem_euclid(128) as u32);
+        let word = |i: isize| {
+            usize::try_from(i)
+                .ok()
+                .and_then(|i| self.0.get(i))
+                .copied()
+                .unwrap_or(0)
+        };
+        Self(std::array::from_fn(|i| {
+            let i = i.cast_signed() + words;
+            word(i) >> amount | word(i + 1).checked_shl(128 - amount).unwrap_or(0)
+        }))
+    }
+}
+
+impl BitAnd for Bits {
+    type Output = Self;
+
+    fn bitand(self, other: Self) -> Self {
+        Self(std::array::from_fn(|i| self.0[i] & other.0[i]))
+    }
+}
+
+impl BitOr for Bits {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(std::array::from_fn(|i| self.0[i] | other.0[i]))
+    }
+}
+
+impl Not for Bits {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self(self.0.map(|word| !word))
+    }
+}
+
+/// The cells of each team as bits, row by row from the top with a spare column after each row,
+/// so that lines never wrap from one row into the next. A line in some direction is then the
+/// same bits shifted by a fixed step, and bits come in the order `check_winner` scans cells.
+#[derive(Clone, Copy, Debug)]
+struct Bitboard {
+    width: usize,
+    // every cell of the board
+    cells: Bits,
+    cookie: Bits,
+    milk: Bits,
+}
+
+impl Bitboard {
+    fn new(size: BoardSize) -> Self {
+        let mut cells = Bits::default();
+        for row in 0..size.height {
+            for column in 0..size.width {
+                cells = cells | Bits::bit(row * (size.width + 1) + column);
+            }
+        }
+        Self {
+            width: size.width,
+            cells,
+            cookie: Bits::default(),
+            milk: Bits::default(),
+        }
+    }
+
+    fn bit(&self, row: usize, column: usize) -> Bits {
+        Bits::bit(row * (self.width + 1) + column)
+    }
+
+    fn set(&mut self, row: usize, column: usize, token: Token) {
+        let bit = self.bit(row, column);
+        self.cookie = self.cookie & !bit;
+        self.milk = self.milk & !bit;
+        match token {
+            Token::Cookie => self.cookie = self.cookie | bit,
+            Token::Milk => self.milk = self.milk | bit,
+            Token::Empty => {}
+        }
+    }
+
+    fn team(&self, token: Token) -> Bits {
+        match token {
+            Token::Cookie => self.cookie,
+            Token::Milk => self.milk,
+            Token::Empty => Bits::default(),
+        }
+    }
+
+    /// How far apart in bits neighbouring cells are in `direction`.
+    fn step(&self, (dr, dc): (isize, isize)) -> isize {
+        dr * (self.width.cast_signed() + 1) + dc
+    }
+
+    /// The cells of `bits` that start a line of `connect` going `step` bits at a time.
+    fn line_starts(bits: Bits, step: isize, connect: usize) -> Bits {
+        (1..connect.cast_signed()).fold(bits, |starts, i| starts & bits.shift(step * i))
+    }
+
+    /// The same result as scanning the cells, including which team wins when both have a line.
+    fn winner(&self, connect: usize) -> Option<Token> {
+        for direction in DIRECTIONS {
+            let step = self.step(direction);
+            let cookie = Self::line_starts(self.cookie, step, connect);
+            let milk = Self::line_starts(self.milk, step, connect);
+            if !cookie.is_empty()
+                && (milk.is_empty() || cookie.trailing_zeros() < milk.trailing_zeros())
+            {
+                return Some(Token::Cookie);
+            } else if !milk.is_empty() {
+                return Some(Token::Milk);
+            }
+        }
+        ((self.cookie | self.milk) == self.cells).then_some(Token::Empty)
+    }
+
+    /// Whether the `token` at (`row`, `column`) is part of a line of `connect`.
+    fn wins_at(&self, row: usize, column: usize, token: Token, connect: usize) -> bool {
+        let bits = self.team(token);
+        let bit = self.bit(row, column);
+        let run = |step: isize| {
+            (1..)
+                .take_while(|&i| !(bits.shift(step * i) & bit).is_empty())
+                .count()
+        };
+        !(bits & bit).is_empty()
+            && DIRECTIONS.iter().any(|&direction| {
+                let step = self.step(direction);
+                1 + run(step) + run(-step) >= connect
+            })
+    }
+
+    /// The square of the `team` tokens in each window of `connect` cells without the other team,
+    /// summed over every window in every direction.
+    fn windows(&self, team: Token, connect: usize) -> i32 {
+        let own = self.team(team);
+        let open = self.cells & !self.team(team.opponent());
+        let mut score = 0;
+        for direction in DIRECTIONS {
+            let step = self.step(direction);
+            let starts = Self::line_starts(open, step, connect);
+            let token = |i: usize| own.shift(step * i.cast_signed());
+            // The square counts the pairs of tokens in a window, each token with itself too.
+            for i in 0..connect {
+                let first = starts & token(i);
+                score += first.count_ones();
+                for j in i + 1..connect {
+                    score += 2 * (first & token(j)).count_ones();
+                }
+            }
+        }
+        score.cast_signed()
+    }
+}
+
 impl BoardImpl {
     fn new() -> Self {
+        Self::with_size(BoardSize::default())
+    }
+
+    fn with_size(size: BoardSize) -> Self {
         Self {
-            inner: [[Token::Empty; 4]; 4],
+            width: size.width,
+            height: size.height,
+            connect: size.connect,
+            inner: vec![vec![Token::Empty; size.width]; size.height],
+            bits: Bitboard::new(size),
+            moves: vec![],
+            randomized: false,
             rng: rand::rngs::StdRng::seed_from_u64(2024),
         }
     }
 
+    fn size(&self) -> BoardSize {
+        BoardSize {
+            width: self.width,
+            height: self.height,
+            connect: self.connect,
+        }
+    }
+
     fn print(&self) -> String {
         let mut result = String::new();
 
-        for row in self.inner {
+        for row in &self.inner {
             // Add the left border.
             result.push('⬜');
-            for token in row {
+            for &token in row {
                 result.push(token.into());
             }
             // Add the right border.
@@ -53,7 +358,8 @@ impl BoardImpl {
         }
 
         // Add the bottom border.
-        result.push_str("⬜⬜⬜⬜⬜⬜\n");
+        result.extend(std::iter::repeat_n('⬜', self.width + 2));
+        result.push('\n');
         match self.check_winner() {
             Some(winner @ (Token::Cookie | Token::Milk)) => {
                 result.push(winner.into());
@@ -65,136 +371,2333 @@ impl BoardImpl {
         result
     }
 
-    fn place(&mut self, pos: usize, token: Token) {
+    /// Drops `token` into column `pos`, returning false when the column is already full.
+    fn place(&mut self, pos: usize, token: Token) -> bool {
         for i in (0..self.inner.len()).rev() {
             if matches!(self.inner[i][pos], Token::Empty) {
-                self.inner[i][pos] = token;
-                return;
+                self.set(i, pos, token);
+                self.moves.push(Move {
+                    team: token,
+                    column: pos,
+                });
+                return true;
             }
         }
+        false
+    }
+
+    fn set(&mut self, row: usize, column: usize, token: Token) {
+        self.inner[row][column] = token;
+        self.bits.set(row, column, token);
+    }
+
+    fn is_column_full(&self, pos: usize) -> bool {
+        !matches!(self.inner[0][pos], Token::Empty)
+    }
+
+    /// Columns, numbered from 1 like in `/place`, that can still take a token.
+    fn legal_columns(&self) -> Vec<usize> {
+        if self.check_winner().is_some() {
+            return vec![];
+        }
+        (0..self.width)
+            .filter(|&pos| !self.is_column_full(pos))
+            .map(|pos| pos + 1)
+            .collect()
     }
 
     fn check_winner(&self) -> Option<Token> {
-        if !matches!(self.inner[0][0], Token::Empty)
-            && self.inner[0][0] == self.inner[1][1]
-            && self.inner[1][1] == self.inner[2][2]
-            && self.inner[2][2] == self.inner[3][3]
-        {
-            // Diagonal top left to bottom right (easier to hardcode for this case)
-            Some(self.inner[0][0])
-        } else if !matches!(self.inner[3][0], Token::Empty)
-            && self.inner[3][0] == self.inner[2][1]
-            && self.inner[2][1] == self.inner[1][2]
-            && self.inner[1][2] == self.inner[0][3]
-        {
-            // Diagonal bottom left to top right (easier to hardcode for this case)
-            Some(self.inner[3][0])
-        } else if let Some(winner) = self.inner.iter().find(|x| {
-            x.iter()
-                .all(|&token| token == x[0] && !matches!(token, Token::Empty))
-        }) {
-            // Horizontal check
-            Some(winner[0])
-        } else if let Some(winner) = (0..self.inner.len()).find(|&i| {
-            !matches!(self.inner[0][i], Token::Empty)
-                && self.inner[0][i] == self.inner[1][i]
-                && self.inner[1][i] == self.inner[2][i]
-                && self.inner[2][i] == self.inner[3][i]
-        }) {
-            // Vertical check
-            Some(self.inner[0][winner])
-        } else if self
-            .inner
-            .iter()
-            .all(|x| x.iter().all(|&token| !matches!(token, Token::Empty)))
-        {
-            // No one won
-            Some(Token::Empty)
+        self.bits.winner(self.connect)
+    }
+
+    /// The team expected to play next when turns alternate, cookie first. `None` once the game
+    /// is over.
+    fn whose_turn(&self) -> Option<Token> {
+        if self.check_winner().is_some() {
+            return None;
+        }
+        let count = |team| self.inner.iter().flatten().filter(|&&t| t == team).count();
+        if count(Token::Cookie) > count(Token::Milk) {
+            Some(Token::Milk)
         } else {
-            // Not yet a winner
-            None
+            Some(Token::Cookie)
         }
     }
 
-    fn reset(&mut self) {
-        *self = Self::new();
+    fn reset(&mut self, size: BoardSize) {
+        *self = Self::with_size(size);
     }
 }
 
-type Board = Arc<Mutex<BoardImpl>>;
+impl BoardImpl {
+    /// Takes back the last move, if the board has a move history.
+    fn undo(&mut self) -> Option<Move> {
+        let last = self.moves.pop()?;
+        let row =
+            (0..self.height).find(|&row| !matches!(self.inner[row][last.column], Token::Empty))?;
+        self.set(row, last.column, Token::Empty);
+        Some(last)
+    }
 
-#[handler]
-fn show_board(Data(board): Data<&Board>) -> String {
-    board.lock().unwrap().print()
+    /// The board after each move, starting from the empty board. `None` for randomized boards,
+    /// which have no history.
+    fn replay(&self) -> Option<Vec<BoardImpl>> {
+        if self.randomized {
+            return None;
+        }
+        let mut board = Self::with_size(self.size());
+        let mut states = vec![board.clone()];
+        for m in &self.moves {
+            board.place(m.column, m.team);
+            states.push(board.clone());
+        }
+        Some(states)
+    }
+
+    /// The game in compact notation: `WxHcN:` followed by `c` (cookie) or `m` (milk) and the
+    /// column, from 1, of each move. For example `7x6c4:c4m4c3`. `None` for randomized boards.
+    fn export(&self) -> Option<String> {
+        if self.randomized {
+            return None;
+        }
+        let mut notation = format!("{}x{}c{}:", self.width, self.height, self.connect);
+        for m in &self.moves {
+            notation.push(if m.team == Token::Milk { 'm' } else { 'c' });
+            notation.push_str(&(m.column + 1).to_string());
+        }
+        Some(notation)
+    }
+
+    /// Rebuilds a game from the notation written by `export`, checking every move.
+    fn import(notation: &str) -> Result<Self, String> {
+        let invalid = || "Expected a game like 7x6c4:c4m4c3".to_string();
+        let (size, mut rest) = notation.trim().split_once(':').ok_or_else(invalid)?;
+        let (width, size) = size.split_once('x').ok_or_else(invalid)?;
+        let (height, connect) = size.split_once('c').ok_or_else(invalid)?;
+        let size = BoardSize {
+            width: width.parse().map_err(|_| invalid())?,
+            height: height.parse().map_err(|_| invalid())?,
+            connect: connect.parse().map_err(|_| invalid())?,
+        }
+        .validate()?;
+
+        let mut board = Self::with_size(size);
+        for n in 1.. {
+            let mut chars = rest.chars();
+            let team = match chars.next() {
+                None => break,
+                Some('c') => Token::Cookie,
+                Some('m') => Token::Milk,
+                Some(_) => return Err(format!("Move {n}: expected c or m")),
+            };
+            rest = chars.as_str();
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let column = rest[..digits]
+                .parse::<usize>()
+                .map_err(|_| format!("Move {n}: expected a column"))?;
+            rest = &rest[digits..];
+
+            if board.check_winner().is_some() {
+                return Err(format!("Move {n}: the game is already over"));
+            } else if column > board.width || column == 0 {
+                return Err(format!("Move {n}: no column {column}"));
+            } else if !board.place(column - 1, team) {
+                return Err(format!("Move {n}: column {column} is full"));
+            }
+        }
+        Ok(board)
+    }
 }
 
-#[handler]
-fn reset_board(Data(board): Data<&Board>) -> String {
-    let mut board = board.lock().unwrap();
-    board.reset();
-    board.print()
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Difficulty {
+    Easy,
+    #[default]
+    Medium,
+    Hard,
 }
 
-#[derive(Deserialize, Debug)]
-struct PlaceBoard {
-    team: Token,
-    position: usize,
+impl Difficulty {
+    /// Plies the computer looks ahead.
+    fn depth(self) -> i32 {
+        match self {
+            Difficulty::Easy => 1,
+            Difficulty::Medium => 4,
+            Difficulty::Hard => 7,
+        }
+    }
 }
 
-#[handler]
-fn place_board(Path(pb): Path<PlaceBoard>, Data(board): Data<&Board>) -> impl IntoResponse {
-    let mut board = board.lock().unwrap();
-    if board.check_winner().is_some() {
-        StatusCode::SERVICE_UNAVAILABLE
-    } else if pb.position >= 5 || pb.position == 0 {
-        StatusCode::BAD_REQUEST
-    } else {
-        board.place(pb.position - 1, pb.team);
+// Bounds for the search scores, small enough to be negated safely.
+const INFINITY: i32 = 1 << 30;
+const WIN: i32 = 1 << 20;
+/// Work the computer may spend on one move. Each position looked at costs its `connect`, since
+/// longer lines make the heuristic slower.
+const SEARCH_BUDGET: u32 = 400_000;
 
-        StatusCode::OK
+impl BoardImpl {
+    /// Row a token dropped into column `pos` would land on.
+    fn landing_row(&self, pos: usize) -> Option<usize> {
+        (0..self.height)
+            .rev()
+            .find(|&row| matches!(self.inner[row][pos], Token::Empty))
     }
-    .with_body(board.print())
-}
 
-#[handler]
-fn random_board(Data(board): Data<&Board>) -> String {
-    let mut board = board.lock().unwrap();
+    /// Whether `team` ends the game by playing `column`, with a line or by filling the board.
+    fn ends_game(&mut self, column: usize, team: Token) -> bool {
+        let Some(row) = self.landing_row(column) else {
+            return false;
+        };
+        self.set(row, column, team);
+        let ends =
+            self.wins_at(row, column) || self.inner[0].iter().all(|&token| token != Token::Empty);
+        self.set(row, column, Token::Empty);
+        ends
+    }
 
-    for i in 0..4 {
-        for j in 0..4 {
-            board.inner[i][j] = if board.rng.gen::<bool>() {
-                Token::Cookie
+    /// Clears the board and plays random legal moves, cookie first, until `fill` of the cells are
+    /// taken or the game is over. With `unfinished`, moves that would end the game are skipped,
+    /// which can leave the board emptier than asked. The same seed always gives the same board.
+    fn play_random(&mut self, seed: u64, fill: f64, unfinished: bool) {
+        self.inner = vec![vec![Token::Empty; self.width]; self.height];
+        self.bits = Bitboard::new(self.size());
+        self.moves.clear();
+        self.randomized = false;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+        let target = (fill * (self.width * self.height) as f64).round() as usize;
+        let mut team = Token::Cookie;
+        while self.moves.len() < target {
+            let columns: Vec<usize> = self
+                .legal_columns()
+                .into_iter()
+                .map(|column| column - 1)
+                .filter(|&column| !unfinished || !self.ends_game(column, team))
+                .collect();
+            let Some(&column) = columns.choose(&mut rng) else {
+                break;
+            };
+            self.place(column, team);
+            team = team.opponent();
+        }
+    }
+
+    /// Whether the token at (`row`, `column`) is part of a line of `connect`.
+    fn wins_at(&self, row: usize, column: usize) -> bool {
+        let token = self.inner[row][column];
+        self.bits.wins_at(row, column, token, self.connect)
+    }
+
+    /// Columns from the center outwards, which are usually the strongest moves and make
+    /// alpha-beta prune earlier.
+    fn move_order(&self) -> Vec<usize> {
+        let mut columns = (0..self.width).collect::<Vec<_>>();
+        columns.sort_by_key(|&column| (2 * column).abs_diff(self.width - 1));
+        columns
+    }
+
+    /// Heuristic for `team`: each window of `connect` cells holding a single team is worth the
+    /// square of its tokens, in favor of that team.
+    fn evaluate(&self, team: Token) -> i32 {
+        self.bits.windows(team, self.connect) - self.bits.windows(team.opponent(), self.connect)
+    }
+
+    /// Negamax with alpha-beta pruning: the score of the position for `team`, to move next,
+    /// looking `depth` plies ahead. Quicker wins score higher. `None` once the search has used up
+    /// its `budget`.
+    fn negamax(
+        &mut self,
+        team: Token,
+        depth: i32,
+        mut alpha: i32,
+        beta: i32,
+        budget: &mut u32,
+    ) -> Option<i32> {
+        *budget = budget.checked_sub(self.connect as u32)?;
+        if depth == 0 {
+            return Some(self.evaluate(team));
+        }
+        let mut best = None;
+        for pos in self.move_order() {
+            let Some(row) = self.landing_row(pos) else {
+                continue;
+            };
+            self.set(row, pos, team);
+            let score = if self.wins_at(row, pos) {
+                Some(WIN + depth)
             } else {
-                Token::Milk
+                self.negamax(team.opponent(), depth - 1, -beta, -alpha, budget)
+                    .map(|score| -score)
+            };
+            self.set(row, pos, Token::Empty);
+            let score = score?;
+
+            best = best.max(Some(score));
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        // A full board is a draw.
+        Some(best.unwrap_or(0))
+    }
+
+    /// The equally scored best columns for `team`, searching `depth` plies ahead. `None` once the
+    /// search has used up its `budget`.
+    fn best_moves(&mut self, team: Token, depth: i32, budget: &mut u32) -> Option<Vec<usize>> {
+        let mut best_score = -INFINITY;
+        let mut best = vec![];
+        for pos in self.move_order() {
+            let Some(row) = self.landing_row(pos) else {
+                continue;
+            };
+            self.set(row, pos, team);
+            // Searching just below the best score keeps the ties exact.
+            let score = if self.wins_at(row, pos) {
+                Some(WIN + depth)
+            } else {
+                let beta = -(best_score - 1);
+                self.negamax(team.opponent(), depth - 1, -INFINITY, beta, budget)
+                    .map(|score| -score)
             };
+            self.set(row, pos, Token::Empty);
+            let score = score?;
+
+            if score > best_score {
+                best_score = score;
+                best = vec![pos];
+            } else if score == best_score {
+                best.push(pos);
+            }
         }
+        best.sort_unstable();
+        Some(best)
     }
 
-    board.print()
+    /// Picks a column for `team`, drawing among the equally scored best moves with the board's
+    /// seeded generator so the choice is reproducible. `None` when every column is full.
+    ///
+    /// The search deepens one ply at a time up to `depth`, and keeps the deepest one that fits
+    /// in `SEARCH_BUDGET`, so that large boards still answer quickly.
+    fn best_move(&mut self, team: Token, depth: i32) -> Option<usize> {
+        let mut budget = SEARCH_BUDGET;
+        let mut best = vec![];
+        for depth in 1..=depth {
+            match self.best_moves(team, depth, &mut budget) {
+                Some(moves) => best = moves,
+                None => break,
+            }
+        }
+        let choice = self.rng.gen_range(0..best.len().max(1));
+        best.get(choice).copied()
+    }
 }
 
-pub(crate) fn route() -> impl Endpoint {
-    Route::new()
-        .at("/board", get(show_board))
-        .at("/reset", post(reset_board))
-        .at("/place/:team/:position", post(place_board))
-        .at("/random-board", get(random_board))
-        .with(AddData::new(Arc::new(Mutex::new(BoardImpl::new()))))
+/// Board representation picked from the `Accept` header: the emoji text, unless JSON is
+/// preferred over plain text.
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Text,
+    Json,
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
+impl<'a> FromRequest<'a> for Format {
+    async fn from_request(req: &'a Request, body: &mut RequestBody) -> poem::Result<Self> {
+        let Accept(accept) = Accept::from_request(req, body).await?;
+        Ok(accept
+            .iter()
+            .find_map(|mime| match mime.essence_str() {
+                "application/json" => Some(Format::Json),
+                "text/plain" | "text/*" | "*/*" => Some(Format::Text),
+                _ => None,
+            })
+            .unwrap_or(Format::Text))
+    }
+}
 
-    #[test]
-    fn test_vertical_win() {
-        let mut board = BoardImpl::new();
+#[derive(Serialize)]
+struct BoardJson {
+    width: usize,
+    height: usize,
+    connect: usize,
+    // rows from top to bottom, `null` for empty cells
+    grid: Vec<Vec<Option<Token>>>,
+    finished: bool,
+    winner: Option<Token>,
+    moves: usize,
+    whose_turn: Option<Token>,
+    legal_columns: Vec<usize>,
+}
 
-        board.place(0, Token::Cookie);
-        board.place(0, Token::Cookie);
-        board.place(0, Token::Cookie);
-        board.place(0, Token::Cookie);
+impl BoardImpl {
+    fn to_json(&self) -> BoardJson {
+        let cell = |token: Token| (token != Token::Empty).then_some(token);
+        let result = self.check_winner();
+        BoardJson {
+            width: self.width,
+            height: self.height,
+            connect: self.connect,
+            grid: self
+                .inner
+                .iter()
+                .map(|row| row.iter().copied().map(cell).collect())
+                .collect(),
+            finished: result.is_some(),
+            winner: result.and_then(cell),
+            moves: self.inner.iter().flatten().filter_map(|&t| cell(t)).count(),
+            whose_turn: self.whose_turn(),
+            legal_columns: self.legal_columns(),
+        }
+    }
 
-        assert_eq!(board.check_winner(), Some(Token::Cookie));
+    fn render(&self, format: Format) -> Response {
+        match format {
+            Format::Text => self.print().into_response(),
+            Format::Json => Json(self.to_json()).into_response(),
+        }
+    }
+
+    /// Checks that a `team` token may go in `column`, counted from 1.
+    fn check_move(&self, team: Token, column: usize) -> Result<(), (StatusCode, String)> {
+        if matches!(team, Token::Empty) {
+            Err((
+                StatusCode::BAD_REQUEST,
+                "Tokens are cookie or milk".to_string(),
+            ))
+        } else if self.check_winner().is_some() {
+            Err((
+                StatusCode::SERVICE_UNAVAILABLE,
+                "The game is over".to_string(),
+            ))
+        } else if column > self.width || column == 0 {
+            Err((StatusCode::BAD_REQUEST, format!("No column {column}")))
+        } else if self.is_column_full(column - 1) {
+            Err((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                format!("Column {column} is full"),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Pushes board states, as JSON, to everyone watching a board over WebSocket.
+#[derive(Clone)]
+struct Updates(broadcast::Sender<String>);
+
+impl Default for Updates {
+    fn default() -> Self {
+        Self(broadcast::channel(16).0)
+    }
+}
+
+impl Updates {
+    /// Sends the board to its watchers if `response` says it was changed.
+    fn publish(&self, board: &BoardImpl, response: Response) -> Response {
+        if response.status().is_success() {
+            self.send(board);
+        }
+        response
+    }
+
+    fn send(&self, board: &BoardImpl) {
+        // An error only means nobody is watching.
+        let _ = self.0.send(board_message(board));
+    }
+}
+
+fn board_message(board: &BoardImpl) -> String {
+    serde_json::to_string(&board.to_json()).unwrap()
+}
+
+type Board = Arc<Mutex<BoardImpl>>;
+
+/// How long a game may go untouched before it is dropped.
+const GAME_TTL: Duration = Duration::from_secs(30 * 60);
+
+struct Game {
+    board: BoardImpl,
+    seats: Seats,
+    updates: Updates,
+    // bumped on every change, so `Games` knows when to save the game
+    version: u64,
+    // the outcome of the move that ended the game, until `Games` records it
+    finished: Option<Token>,
+    last_access: Instant,
+}
+
+impl Game {
+    fn new(board: BoardImpl, seats: Seats) -> Self {
+        Self {
+            board,
+            seats,
+            updates: Updates::default(),
+            version: 0,
+            finished: None,
+            last_access: Instant::now(),
+        }
+    }
+
+    /// Sends the board to the game's watchers and marks the game for saving.
+    fn changed(&mut self) {
+        self.version += 1;
+        self.updates.send(&self.board);
+    }
+
+    /// Calls `changed` if `response` says the game was changed.
+    fn publish(&mut self, response: Response) -> Response {
+        if response.status().is_success() {
+            self.changed();
+        }
+        response
+    }
+
+    /// Places a token for `occupant`, checking the board before the seats and turns.
+    fn play(
+        &mut self,
+        team: Token,
+        column: usize,
+        occupant: Seat,
+    ) -> Result<(), (StatusCode, String)> {
+        self.board.check_move(team, column)?;
+        self.take_turn(team, occupant)?;
+        self.board.place(column - 1, team);
+        self.finished = self.board.check_winner();
+        Ok(())
+    }
+
+    /// Checks that `occupant` holds one of the game's seats.
+    fn check_player(&self, occupant: &Seat) -> Result<(), (StatusCode, String)> {
+        if self.seats.get(Token::Cookie) == Some(occupant)
+            || self.seats.get(Token::Milk) == Some(occupant)
+        {
+            Ok(())
+        } else {
+            Err((
+                StatusCode::FORBIDDEN,
+                "Only players of this game can change it".to_string(),
+            ))
+        }
+    }
+
+    /// Checks that `occupant` may move for `team` now.
+    fn check_turn(&self, team: Token, occupant: &Seat) -> Result<(), (StatusCode, String)> {
+        self.seats
+            .check(team, occupant)
+            .map_err(|err| (StatusCode::FORBIDDEN, err))?;
+        if let Some(turn) = self.board.whose_turn().filter(|&turn| turn != team) {
+            return Err((
+                StatusCode::CONFLICT,
+                format!("It is {}'s turn", char::from(turn)),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that `occupant` may move for `team` now, claiming the seat if it is free.
+    fn take_turn(&mut self, team: Token, occupant: Seat) -> Result<(), (StatusCode, String)> {
+        self.check_turn(team, &occupant)?;
+        self.seats.seat(team).get_or_insert(occupant);
+        Ok(())
+    }
+
+    /// Checks that `occupant` made the last move and that the game is not over yet.
+    fn check_undo(&self, occupant: &Seat) -> Result<(), (StatusCode, String)> {
+        self.check_player(occupant)?;
+        if self.board.check_winner().is_some() {
+            return Err((StatusCode::CONFLICT, "The game is over".to_string()));
+        }
+        match self.board.moves.last() {
+            Some(last) if self.seats.get(last.team) != Some(occupant) => Err((
+                StatusCode::FORBIDDEN,
+                "Only the player who made the last move can undo it".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Seat {
+    Player(String),
+    Computer,
+}
+
+/// Who holds each team of a game. A seat goes to the first one to move for that team.
+#[derive(Default)]
+struct Seats {
+    cookie: Option<Seat>,
+    milk: Option<Seat>,
+}
+
+impl Seats {
+    fn get(&self, team: Token) -> Option<&Seat> {
+        match team {
+            Token::Milk => self.milk.as_ref(),
+            Token::Cookie | Token::Empty => self.cookie.as_ref(),
+        }
+    }
+
+    fn seat(&mut self, team: Token) -> &mut Option<Seat> {
+        match team {
+            Token::Milk => &mut self.milk,
+            Token::Cookie | Token::Empty => &mut self.cookie,
+        }
+    }
+
+    /// Checks that `occupant` may move for `team`, which is the case if its seat is free.
+    /// Players may only hold one team, while the computer can play against itself.
+    fn check(&self, team: Token, occupant: &Seat) -> Result<(), String> {
+        let other = team.opponent();
+        if matches!(occupant, Seat::Player(_)) && self.get(other) == Some(occupant) {
+            return Err(format!("You are playing {}", char::from(other)));
+        }
+        match self.get(team) {
+            Some(owner) if owner != occupant => {
+                Err(format!("{} is played by someone else", char::from(team)))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Identifies who is playing: the `sub` of a bearer token signed with the `PlayerKey`, or
+/// else the `player` cookie, which is handed out on the first request without one.
+struct Player(String);
+
+const PLAYER_COOKIE: &str = "player";
+
+/// The key of the bearer tokens identifying players, from `PLAYER_TOKEN_SECRET`. Without it,
+/// players are only known by their cookie.
+#[derive(Clone, Default)]
+struct PlayerKey(Option<jsonwebtoken::DecodingKey>);
+
+impl PlayerKey {
+    fn from_env() -> Self {
+        let secret = std::env::var("PLAYER_TOKEN_SECRET").ok();
+        Self(
+            secret
+                .filter(|secret| !secret.is_empty())
+                .map(|secret| jsonwebtoken::DecodingKey::from_secret(secret.as_bytes())),
+        )
+    }
+
+    fn verify(&self, token: &str) -> Option<Player> {
+        let mut validation = jsonwebtoken::Validation::default();
+        validation.required_spec_claims.clear();
+        let token =
+            jsonwebtoken::decode::<serde_json::Value>(token, self.0.as_ref()?, &validation).ok()?;
+        Some(Player(token.claims.get("sub")?.as_str()?.to_string()))
+    }
+}
+
+impl<'a> FromRequest<'a> for Player {
+    async fn from_request(req: &'a Request, _body: &mut RequestBody) -> poem::Result<Self> {
+        if let Some(token) = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|x| x.to_str().ok())
+            .and_then(|x| x.strip_prefix("Bearer "))
+        {
+            return req
+                .data::<PlayerKey>()
+                .and_then(|key| key.verify(token))
+                .ok_or_else(|| {
+                    poem::Error::from_string("Invalid player token", StatusCode::UNAUTHORIZED)
+                });
+        }
+
+        let cookies = req.cookie();
+        if let Some(cookie) = cookies.get(PLAYER_COOKIE) {
+            return Ok(Player(cookie.value_str().to_string()));
+        }
+        let id = Builder::from_random_bytes(rand::random())
+            .into_uuid()
+            .to_string();
+        cookies.add(Cookie::new_with_str(PLAYER_COOKIE, &id));
+        Ok(Player(id))
+    }
+}
+
+/// Games created through `/games`, each with its own board. With a store, every change is
+/// also saved to the database.
+#[derive(Default)]
+struct Games {
+    games: Mutex<HashMap<Uuid, Game>>,
+    store: Option<Store>,
+    // feeds the task writing to `store`
+    pending: Option<Arc<Pending>>,
+}
+
+#[derive(Serialize)]
+struct GameSummary {
+    id: Uuid,
+    width: usize,
+    height: usize,
+    connect: usize,
+    finished: bool,
+}
+
+impl GameSummary {
+    fn new(id: Uuid, board: &BoardImpl) -> Self {
+        Self {
+            id,
+            width: board.width,
+            height: board.height,
+            connect: board.connect,
+            finished: board.check_winner().is_some(),
+        }
+    }
+}
+
+impl Games {
+    /// Locks the games, dropping the ones idle for longer than `GAME_TTL`.
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<Uuid, Game>> {
+        let mut games = self.games.lock().unwrap();
+        games.retain(|_, game| game.last_access.elapsed() < GAME_TTL);
+        games
+    }
+
+    fn create(&self, board: BoardImpl) -> GameSummary {
+        let id = Builder::from_random_bytes(rand::random()).into_uuid();
+        let summary = GameSummary::new(id, &board);
+        let game = Game::new(board, Seats::default());
+        self.save(id, &game);
+        self.lock().insert(id, game);
+        summary
+    }
+
+    /// Runs `f` on game `id`, refreshing its idle timer and saving the game if `f` changed it,
+    /// along with its result if `f` ended it.
+    fn with_game<R>(&self, id: Uuid, f: impl FnOnce(&mut Game) -> R) -> Option<R> {
+        let mut games = self.lock();
+        let game = games.get_mut(&id)?;
+        game.last_access = Instant::now();
+        let version = game.version;
+        let result = f(game);
+        if game.version != version {
+            if let (Some(outcome), Some(pending)) = (game.finished.take(), &self.pending) {
+                pending.record(GameResult::new(id, outcome, &game.seats));
+            }
+            self.save(id, game);
+        }
+        Some(result)
+    }
+
+    fn save(&self, id: Uuid, game: &Game) {
+        if let Some(pending) = &self.pending {
+            pending.push(id, Change::Save(SavedGame::new(id, game)));
+        }
+    }
+
+    /// Runs `f` on the board of game `id`, refreshing its idle timer.
+    fn with<R>(&self, id: Uuid, f: impl FnOnce(&mut BoardImpl) -> R) -> Option<R> {
+        self.with_game(id, |game| f(&mut game.board))
+    }
+
+    fn list(&self) -> Vec<GameSummary> {
+        self.lock()
+            .iter()
+            .map(|(&id, game)| GameSummary::new(id, &game.board))
+            .collect()
+    }
+
+    /// Removes game `id` if `occupant` holds one of its seats.
+    fn remove(&self, id: Uuid, occupant: &Seat) -> Option<Result<(), (StatusCode, String)>> {
+        let mut games = self.lock();
+        let checked = games.get(&id)?.check_player(occupant);
+        if checked.is_ok() {
+            games.remove(&id);
+            if let Some(pending) = &self.pending {
+                pending.push(id, Change::Delete(id));
+            }
+        }
+        Some(checked)
+    }
+
+    /// Saves games through `store` from now on, after loading the recent ones back. Changes are
+    /// written by a single task, so requests never wait for the database.
+    fn persist(pool: sqlx::PgPool) -> Arc<Self> {
+        let pending = Arc::new(Pending::default());
+        let store = Store { pool };
+        let games = Arc::new(Self {
+            games: Mutex::default(),
+            store: Some(store.clone()),
+            pending: Some(pending.clone()),
+        });
+        let loaded = games.clone();
+        tokio::spawn(async move {
+            match store.load().await {
+                Ok(saved) => {
+                    let mut games = loaded.lock();
+                    for (id, game) in saved {
+                        games.entry(id).or_insert(game);
+                    }
+                }
+                Err(err) => eprintln!("connect four store err {err}"),
+            }
+            drop(loaded);
+            // The writer only stops with the runtime.
+            loop {
+                pending.ready.notified().await;
+                let (changes, results) = pending.take();
+                for change in changes {
+                    if let Err(err) = store.apply(change).await {
+                        eprintln!("connect four store err {err}");
+                    }
+                }
+                if results.is_empty() {
+                    continue;
+                }
+                if let Err(err) = store.record(results).await {
+                    eprintln!("connect four store err {err}");
+                }
+            }
+        });
+        games
+    }
+}
+
+/// Changes waiting for the task writing them to the store. Only the latest one of each game is
+/// kept, as each holds the whole game, so a slow database costs at most one per game. Results
+/// are all kept, but it takes a whole game to add one.
+#[derive(Default)]
+struct Pending {
+    changes: Mutex<HashMap<Uuid, Change>>,
+    results: Mutex<Vec<GameResult>>,
+    ready: Notify,
+}
+
+impl Pending {
+    fn push(&self, id: Uuid, change: Change) {
+        self.changes.lock().unwrap().insert(id, change);
+        self.ready.notify_one();
+    }
+
+    fn record(&self, result: GameResult) {
+        self.results.lock().unwrap().push(result);
+        self.ready.notify_one();
+    }
+
+    fn take(&self) -> (Vec<Change>, Vec<GameResult>) {
+        let changes = std::mem::take(&mut *self.changes.lock().unwrap());
+        let results = std::mem::take(&mut *self.results.lock().unwrap());
+        (changes.into_values().collect(), results)
+    }
+}
+
+/// A public name for `player`: the start of a hash of its id, which stays secret since anyone
+/// presenting it plays as them.
+fn handle(player: &str) -> String {
+    Sha256::digest(player.as_bytes())[..8]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// How a game ended, as stored in the append-only `connect_results` table.
+#[derive(Debug, PartialEq)]
+struct GameResult {
+    game_id: Uuid,
+    outcome: &'static str,
+    // the `handle` of the player of each team, `None` for the computer
+    cookie: Option<String>,
+    milk: Option<String>,
+}
+
+impl GameResult {
+    fn new(game_id: Uuid, outcome: Token, seats: &Seats) -> Self {
+        let player = |team| match seats.get(team)? {
+            Seat::Player(player) => Some(handle(player)),
+            Seat::Computer => None,
+        };
+        Self {
+            game_id,
+            outcome: outcome.name(),
+            cookie: player(Token::Cookie),
+            milk: player(Token::Milk),
+        }
+    }
+}
+
+enum Change {
+    Save(SavedGame),
+    Delete(Uuid),
+}
+
+/// A game as stored in the `connect_games`, `connect_moves` and `connect_seats` tables.
+struct SavedGame {
+    id: Uuid,
+    width: i32,
+    height: i32,
+    connect: i32,
+    grid: String,
+    randomized: bool,
+    outcome: Option<&'static str>,
+    moves: Vec<Move>,
+    seats: Vec<(Token, Option<String>)>,
+}
+
+impl SavedGame {
+    fn new(id: Uuid, game: &Game) -> Self {
+        let board = &game.board;
+        let seats = [Token::Cookie, Token::Milk]
+            .into_iter()
+            .filter_map(|team| {
+                let player = match game.seats.get(team)? {
+                    Seat::Player(player) => Some(player.clone()),
+                    Seat::Computer => None,
+                };
+                Some((team, player))
+            })
+            .collect();
+        Self {
+            id,
+            // sides are at most `MAX_SIDE`
+            width: board.width as i32,
+            height: board.height as i32,
+            connect: board.connect as i32,
+            grid: board
+                .inner
+                .iter()
+                .flatten()
+                .map(|&token| match token {
+                    Token::Cookie => 'c',
+                    Token::Milk => 'm',
+                    Token::Empty => '.',
+                })
+                .collect(),
+            randomized: board.randomized,
+            outcome: board.check_winner().map(Token::name),
+            moves: board.moves.clone(),
+            seats,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Store {
+    pool: sqlx::PgPool,
+}
+
+impl Store {
+    async fn apply(&self, change: Change) -> Result<(), sqlx::Error> {
+        match change {
+            Change::Save(game) => self.save(game).await,
+            Change::Delete(id) => {
+                sqlx::query!("DELETE FROM connect_games WHERE id = $1", id)
+                    .execute(&self.pool)
+                    .await?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Replaces the stored game, its moves and its seats.
+    async fn save(&self, game: SavedGame) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query!(
+            "INSERT INTO connect_games (id, width, height, connect, grid, randomized, outcome)
+             VALUES ($1, $2, $3, $4, $5, $6, $7)
+             ON CONFLICT (id) DO UPDATE
+             SET width = $2, height = $3, connect = $4, grid = $5, randomized = $6, outcome = $7,
+                 updated_at = now()",
+            game.id,
+            game.width,
+            game.height,
+            game.connect,
+            game.grid,
+            game.randomized,
+            game.outcome,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let plies: Vec<i32> = (1..).take(game.moves.len()).collect();
+        let teams: Vec<String> = game
+            .moves
+            .iter()
+            .map(|m| m.team.name().to_string())
+            .collect();
+        let columns: Vec<i32> = game.moves.iter().map(|m| m.column as i32 + 1).collect();
+        sqlx::query!("DELETE FROM connect_moves WHERE game_id = $1", game.id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!(
+            "INSERT INTO connect_moves (game_id, ply, team, col)
+             SELECT $1, * FROM UNNEST($2::INT[], $3::TEXT[], $4::INT[])",
+            game.id,
+            &plies,
+            &teams,
+            &columns,
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let (teams, players): (Vec<String>, Vec<Option<String>>) = game
+            .seats
+            .into_iter()
+            .map(|(team, player)| (team.name().to_string(), player))
+            .unzip();
+        sqlx::query!("DELETE FROM connect_seats WHERE game_id = $1", game.id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!(
+            "INSERT INTO connect_seats (game_id, team, player)
+             SELECT $1, * FROM UNNEST($2::TEXT[], $3::TEXT[])",
+            game.id,
+            &teams,
+            &players as &[Option<String>],
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await
+    }
+
+    async fn record(&self, results: Vec<GameResult>) -> Result<(), sqlx::Error> {
+        let mut ids = Vec::with_capacity(results.len());
+        let mut outcomes = Vec::with_capacity(results.len());
+        let mut cookies = Vec::with_capacity(results.len());
+        let mut milks = Vec::with_capacity(results.len());
+        for result in results {
+            ids.push(result.game_id);
+            outcomes.push(result.outcome.to_string());
+            cookies.push(result.cookie);
+            milks.push(result.milk);
+        }
+        sqlx::query!(
+            "INSERT INTO connect_results (game_id, outcome, cookie, milk)
+             SELECT * FROM UNNEST($1::UUID[], $2::TEXT[], $3::TEXT[], $4::TEXT[])",
+            &ids,
+            &outcomes,
+            &cookies as &[Option<String>],
+            &milks as &[Option<String>],
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// The games changed within `GAME_TTL`, which would not have expired had the process kept
+    /// running.
+    async fn load(&self) -> Result<Vec<(Uuid, Game)>, sqlx::Error> {
+        let ttl = GAME_TTL.as_secs_f64();
+        let rows = sqlx::query!(
+            "SELECT id, width, height, connect, grid, randomized FROM connect_games
+             WHERE updated_at > now() - make_interval(secs => $1)",
+            ttl,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let ids: Vec<Uuid> = rows.iter().map(|row| row.id).collect();
+        let moves = sqlx::query!(
+            "SELECT game_id, team, col FROM connect_moves WHERE game_id = ANY($1) ORDER BY ply",
+            &ids,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let seats = sqlx::query!(
+            "SELECT game_id, team, player FROM connect_seats WHERE game_id = ANY($1)",
+            &ids,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut games: HashMap<Uuid, Game> = HashMap::new();
+        for row in rows {
+            let size = BoardSize {
+                width: row.width as usize,
+                height: row.height as usize,
+                connect: row.connect as usize,
+            };
+            let mut board = BoardImpl::with_size(size);
+            let mut cells = row.grid.chars();
+            for i in 0..board.height {
+                for j in 0..board.width {
+                    let token = match cells.next() {
+                        Some('c') => Token::Cookie,
+                        Some('m') => Token::Milk,
+                        _ => Token::Empty,
+                    };
+                    board.set(i, j, token);
+                }
+            }
+            board.randomized = row.randomized;
+            games.insert(row.id, Game::new(board, Seats::default()));
+        }
+        for m in moves {
+            if let Some(game) = games.get_mut(&m.game_id) {
+                game.board.moves.push(Move {
+                    team: Token::from_name(&m.team),
+                    column: m.col as usize - 1,
+                });
+            }
+        }
+        for seat in seats {
+            if let Some(game) = games.get_mut(&seat.game_id) {
+                *game.seats.seat(Token::from_name(&seat.team)) =
+                    Some(seat.player.map_or(Seat::Computer, Seat::Player));
+            }
+        }
+        Ok(games.into_iter().collect())
+    }
+
+    async fn leaderboard(&self) -> Result<Leaderboard, sqlx::Error> {
+        let teams = sqlx::query_as!(
+            TeamStats,
+            r#"SELECT team AS "team!",
+                      COUNT(*) FILTER (WHERE outcome = team) AS "wins!",
+                      COUNT(*) FILTER (WHERE outcome NOT IN (team, 'draw')) AS "losses!",
+                      COUNT(*) FILTER (WHERE outcome = 'draw') AS "draws!"
+               FROM connect_results, (VALUES ('cookie'), ('milk')) AS teams (team)
+               GROUP BY team
+               ORDER BY 2 DESC, 3, team"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let players = sqlx::query_as!(
+            PlayerStats,
+            r#"SELECT player AS "player!",
+                      COUNT(*) FILTER (WHERE outcome = team) AS "wins!",
+                      COUNT(*) FILTER (WHERE outcome NOT IN (team, 'draw')) AS "losses!",
+                      COUNT(*) FILTER (WHERE outcome = 'draw') AS "draws!"
+               FROM connect_results,
+                    LATERAL (VALUES ('cookie', cookie), ('milk', milk)) AS seats (team, player)
+               WHERE player IS NOT NULL
+               GROUP BY player
+               ORDER BY 2 DESC, 3, 4 DESC, player
+               LIMIT 100"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(Leaderboard { teams, players })
+    }
+}
+
+/// Games ended by a move, ranked by wins and then by fewest losses.
+#[derive(Serialize)]
+struct Leaderboard {
+    teams: Vec<TeamStats>,
+    players: Vec<PlayerStats>,
+}
+
+#[derive(Serialize)]
+struct TeamStats {
+    team: String,
+    wins: i64,
+    losses: i64,
+    draws: i64,
+}
+
+#[derive(Serialize)]
+struct PlayerStats {
+    // the player's `handle`
+    player: String,
+    wins: i64,
+    losses: i64,
+    draws: i64,
+}
+
+/// Optional board configuration for `/reset`; missing values keep the current ones.
+#[derive(Deserialize, Debug)]
+struct ResetBoard {
+    width: Option<usize>,
+    height: Option<usize>,
+    connect: Option<usize>,
+}
+
+impl ResetBoard {
+    fn size(&self, current: BoardSize) -> Result<BoardSize, String> {
+        BoardSize {
+            width: self.width.unwrap_or(current.width),
+            height: self.height.unwrap_or(current.height),
+            connect: self.connect.unwrap_or(current.connect),
+        }
+        .validate()
+    }
+}
+
+fn refuse((status, err): (StatusCode, String)) -> Response {
+    status.with_body(format!("{err}\n")).into_response()
+}
+
+fn bad_request(err: &str) -> Response {
+    StatusCode::BAD_REQUEST
+        .with_body(format!("{err}\n"))
+        .into_response()
+}
+
+fn reset(board: &mut BoardImpl, query: &ResetBoard, format: Format) -> Response {
+    match query.size(board.size()) {
+        Ok(size) => {
+            board.reset(size);
+            board.render(format)
+        }
+        Err(err) => bad_request(&err),
+    }
+}
+
+fn place(board: &mut BoardImpl, team: Token, column: usize, format: Format) -> Response {
+    match board.check_move(team, column) {
+        Ok(()) => {
+            board.place(column - 1, team);
+            board.render(format)
+        }
+        Err(err) => rejected(board, err, format),
+    }
+}
+
+/// Finished games and unknown columns answer with the board, other refused moves with the reason.
+fn rejected(board: &BoardImpl, (status, err): (StatusCode, String), format: Format) -> Response {
+    match status {
+        StatusCode::SERVICE_UNAVAILABLE | StatusCode::BAD_REQUEST => {
+            board.render(format).with_status(status).into_response()
+        }
+        _ => refuse((status, err)),
+    }
+}
+
+/// Options for `/random-board`. Without any, every cell is filled from the board's own random
+/// stream, as the challenge expects; with any, a playable position is built from a seed.
+#[derive(Deserialize, Debug)]
+struct RandomBoard {
+    // drawn at random, and returned in `SEED_HEADER`, if missing
+    seed: Option<u64>,
+    // share of the cells to fill, 1 by default
+    fill: Option<f64>,
+    #[serde(default)]
+    unfinished: bool,
+}
+
+const SEED_HEADER: &str = "X-Random-Seed";
+
+fn randomize(board: &mut BoardImpl, query: &RandomBoard, format: Format) -> Response {
+    if query.seed.is_some() || query.fill.is_some() || query.unfinished {
+        let fill = query.fill.unwrap_or(1.0);
+        if !(0.0..=1.0).contains(&fill) {
+            return bad_request("The fill must be between 0 and 1");
+        }
+        let seed = query.seed.unwrap_or_else(rand::random);
+        board.play_random(seed, fill, query.unfinished);
+        return board
+            .render(format)
+            .with_header(SEED_HEADER, seed)
+            .into_response();
+    }
+
+    board.moves.clear();
+    board.randomized = true;
+    for i in 0..board.height {
+        for j in 0..board.width {
+            let token = if board.rng.gen::<bool>() {
+                Token::Cookie
+            } else {
+                Token::Milk
+            };
+            board.set(i, j, token);
+        }
+    }
+
+    board.render(format)
+}
+
+#[handler]
+fn show_board(format: Format, Data(board): Data<&Board>) -> Response {
+    board.lock().unwrap().render(format)
+}
+
+#[handler]
+fn reset_board(
+    Query(query): Query<ResetBoard>,
+    format: Format,
+    Data(board): Data<&Board>,
+    Data(updates): Data<&Updates>,
+) -> Response {
+    let mut board = board.lock().unwrap();
+    let res = reset(&mut board, &query, format);
+    updates.publish(&board, res)
+}
+
+#[derive(Deserialize, Debug)]
+struct PlaceBoard {
+    team: Token,
+    position: usize,
+}
+
+#[handler]
+fn place_board(
+    Path(pb): Path<PlaceBoard>,
+    format: Format,
+    Data(board): Data<&Board>,
+    Data(updates): Data<&Updates>,
+) -> Response {
+    let mut board = board.lock().unwrap();
+    let res = place(&mut board, pb.team, pb.position, format);
+    updates.publish(&board, res)
+}
+
+#[handler]
+fn random_board(
+    Query(query): Query<RandomBoard>,
+    format: Format,
+    Data(board): Data<&Board>,
+    Data(updates): Data<&Updates>,
+) -> Response {
+    let mut board = board.lock().unwrap();
+    let res = randomize(&mut board, &query, format);
+    updates.publish(&board, res)
+}
+
+#[derive(Deserialize, Debug)]
+struct ComputerQuery {
+    #[serde(default)]
+    difficulty: Difficulty,
+}
+
+/// The response refusing to let the computer move for `team`, if it may not.
+fn check_computer(board: &BoardImpl, team: Token, format: Format) -> Option<Response> {
+    if matches!(team, Token::Empty) {
+        return Some(bad_request("The computer plays cookie or milk"));
+    }
+    board.check_winner().is_some().then(|| {
+        board
+            .render(format)
+            .with_status(StatusCode::SERVICE_UNAVAILABLE)
+            .into_response()
+    })
+}
+
+/// Searches the computer's move for `team` on a blocking thread, so that neither the runtime nor
+/// the board's lock wait for it. Returns the board searched, with its generator moved on by the
+/// pick, and the column, `None` when every column is full.
+async fn think(
+    mut board: BoardImpl,
+    team: Token,
+    difficulty: Difficulty,
+) -> (BoardImpl, Option<usize>) {
+    tokio::task::spawn_blocking(move || {
+        let pos = board.best_move(team, difficulty.depth());
+        (board, pos)
+    })
+    .await
+    .unwrap()
+}
+
+/// Checks that no one moved while the computer was thinking on a copy of `board`.
+fn check_unchanged(board: &BoardImpl, searched: &BoardImpl) -> Result<(), (StatusCode, String)> {
+    if board.inner == searched.inner {
+        Ok(())
+    } else {
+        Err((
+            StatusCode::CONFLICT,
+            "The board changed while the computer was thinking".to_string(),
+        ))
+    }
+}
+
+/// Plays the column the computer picked on `searched`, a copy of `board`.
+fn computer_move(
+    board: &mut BoardImpl,
+    searched: BoardImpl,
+    team: Token,
+    pos: Option<usize>,
+    format: Format,
+) -> Response {
+    board.rng = searched.rng;
+    if let Some(pos) = pos {
+        board.place(pos, team);
+    }
+    board.render(format)
+}
+
+#[derive(Deserialize, Debug)]
+struct ComputerBoard {
+    team: Token,
+}
+
+/// Lets the computer move for a team on the free play board.
+#[handler]
+async fn computer_board(
+    Path(cb): Path<ComputerBoard>,
+    Query(query): Query<ComputerQuery>,
+    format: Format,
+    Data(board): Data<&Board>,
+    Data(updates): Data<&Updates>,
+) -> Response {
+    let searched = {
+        let board = board.lock().unwrap();
+        if let Some(res) = check_computer(&board, cb.team, format) {
+            return res;
+        }
+        board.clone()
+    };
+    let (searched, pos) = think(searched, cb.team, query.difficulty).await;
+    let mut board = board.lock().unwrap();
+    if let Err(err) = check_unchanged(&board, &searched) {
+        return refuse(err);
+    }
+    let res = computer_move(&mut board, searched, cb.team, pos, format);
+    updates.publish(&board, res)
+}
+
+fn no_history() -> Response {
+    StatusCode::CONFLICT
+        .with_body("The board was randomized and has no move history\n")
+        .into_response()
+}
+
+fn undo(board: &mut BoardImpl, format: Format) -> Response {
+    if board.randomized {
+        no_history()
+    } else if board.undo().is_none() {
+        StatusCode::CONFLICT
+            .with_body("Nothing to undo\n")
+            .into_response()
+    } else {
+        board.render(format)
+    }
+}
+
+/// Every board state of the game, as a JSON array or as the emoji boards one after another.
+fn replay(board: &BoardImpl, format: Format) -> Response {
+    let Some(states) = board.replay() else {
+        return no_history();
+    };
+    match format {
+        Format::Text => states
+            .iter()
+            .map(BoardImpl::print)
+            .collect::<Vec<_>>()
+            .join("\n")
+            .into_response(),
+        Format::Json => {
+            Json(states.iter().map(BoardImpl::to_json).collect::<Vec<_>>()).into_response()
+        }
+    }
+}
+
+fn export(board: &BoardImpl) -> Response {
+    board
+        .export()
+        .map_or_else(no_history, IntoResponse::into_response)
+}
+
+#[handler]
+fn undo_board(
+    format: Format,
+    Data(board): Data<&Board>,
+    Data(updates): Data<&Updates>,
+) -> Response {
+    let mut board = board.lock().unwrap();
+    let res = undo(&mut board, format);
+    updates.publish(&board, res)
+}
+
+#[handler]
+fn replay_board(format: Format, Data(board): Data<&Board>) -> Response {
+    replay(&board.lock().unwrap(), format)
+}
+
+#[handler]
+fn export_board(Data(board): Data<&Board>) -> Response {
+    export(&board.lock().unwrap())
+}
+
+/// Replaces the free play board with an imported game.
+#[handler]
+fn import_board(
+    body: String,
+    format: Format,
+    Data(board): Data<&Board>,
+    Data(updates): Data<&Updates>,
+) -> Response {
+    match BoardImpl::import(&body) {
+        Ok(imported) => {
+            let mut board = board.lock().unwrap();
+            *board = imported;
+            updates.publish(&board, board.render(format))
+        }
+        Err(err) => bad_request(&err),
+    }
+}
+
+fn game_not_found() -> Response {
+    StatusCode::NOT_FOUND
+        .with_body("Game not found\n")
+        .into_response()
+}
+
+fn created(game: GameSummary) -> Response {
+    let location = format!("/12/games/{}", game.id);
+    Json(game)
+        .with_status(StatusCode::CREATED)
+        .with_header(LOCATION, location)
+        .into_response()
+}
+
+#[handler]
+fn create_game(Query(query): Query<ResetBoard>, Data(games): Data<&Arc<Games>>) -> Response {
+    match query.size(BoardSize::default()) {
+        Ok(size) => created(games.create(BoardImpl::with_size(size))),
+        Err(err) => bad_request(&err),
+    }
+}
+
+/// Creates a game from its notation. Games alternate turns, so the moves must too.
+#[handler]
+fn import_game(body: String, Data(games): Data<&Arc<Games>>) -> Response {
+    let board = match BoardImpl::import(&body) {
+        Ok(board) => board,
+        Err(err) => return bad_request(&err),
+    };
+    let alternates = board.moves.iter().enumerate().all(|(i, m)| {
+        m.team
+            == if i % 2 == 0 {
+                Token::Cookie
+            } else {
+                Token::Milk
+            }
+    });
+    if !alternates {
+        return bad_request("Moves must alternate, cookie first");
+    }
+    created(games.create(board))
+}
+
+#[handler]
+async fn leaderboard(Data(games): Data<&Arc<Games>>) -> Response {
+    let Some(store) = &games.store else {
+        return StatusCode::SERVICE_UNAVAILABLE
+            .with_body("Statistics need the database\n")
+            .into_response();
+    };
+    match store.leaderboard().await {
+        Ok(leaderboard) => Json(leaderboard).into_response(),
+        Err(err) => {
+            eprintln!("connect four store err {err}");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[handler]
+fn list_games(Data(games): Data<&Arc<Games>>) -> Json<Vec<GameSummary>> {
+    Json(games.list())
+}
+
+/// Deletes a game, for players holding one of its seats.
+#[handler]
+fn delete_game(
+    Path(id): Path<Uuid>,
+    Player(player): Player,
+    Data(games): Data<&Arc<Games>>,
+) -> Response {
+    match games.remove(id, &Seat::Player(player)) {
+        Some(Ok(())) => StatusCode::NO_CONTENT.into_response(),
+        Some(Err(err)) => refuse(err),
+        None => game_not_found(),
+    }
+}
+
+#[handler]
+fn show_game(Path(id): Path<Uuid>, format: Format, Data(games): Data<&Arc<Games>>) -> Response {
+    games
+        .with(id, |board| board.render(format))
+        .unwrap_or_else(game_not_found)
+}
+
+/// Resets a game, for players holding one of its seats.
+#[handler]
+fn reset_game(
+    Path(id): Path<Uuid>,
+    Query(query): Query<ResetBoard>,
+    Player(player): Player,
+    format: Format,
+    Data(games): Data<&Arc<Games>>,
+) -> Response {
+    games
+        .with_game(id, |game| {
+            if let Err(err) = game.check_player(&Seat::Player(player)) {
+                return refuse(err);
+            }
+            let res = reset(&mut game.board, &query, format);
+            game.publish(res)
+        })
+        .unwrap_or_else(game_not_found)
+}
+
+#[derive(Deserialize, Debug)]
+struct PlaceGame {
+    id: Uuid,
+    team: Token,
+    column: usize,
+}
+
+/// Places a token in a game, where teams must alternate and each team is held by one player.
+#[handler]
+fn place_game(
+    Path(pg): Path<PlaceGame>,
+    Player(player): Player,
+    format: Format,
+    Data(games): Data<&Arc<Games>>,
+) -> Response {
+    games
+        .with_game(pg.id, |game| {
+            match game.play(pg.team, pg.column, Seat::Player(player)) {
+                Ok(()) => {
+                    let res = game.board.render(format);
+                    game.publish(res)
+                }
+                Err(err) => rejected(&game.board, err, format),
+            }
+        })
+        .unwrap_or_else(game_not_found)
+}
+
+#[derive(Deserialize, Debug)]
+struct ComputerGame {
+    id: Uuid,
+    team: Token,
+}
+
+/// Lets the computer move for a team of a game, taking that team's seat.
+#[handler]
+async fn computer_game(
+    Path(cg): Path<ComputerGame>,
+    Query(query): Query<ComputerQuery>,
+    format: Format,
+    Data(games): Data<&Arc<Games>>,
+) -> Response {
+    let Some((searched, refusal)) = games.with_game(cg.id, |game| {
+        let refusal = check_computer(&game.board, cg.team, format).or_else(|| {
+            let turn = game.check_turn(cg.team, &Seat::Computer);
+            turn.err().map(refuse)
+        });
+        (game.board.clone(), refusal)
+    }) else {
+        return game_not_found();
+    };
+    if let Some(res) = refusal {
+        return res;
+    }
+    let (searched, pos) = think(searched, cg.team, query.difficulty).await;
+    games
+        .with_game(cg.id, |game| {
+            let turn = check_unchanged(&game.board, &searched)
+                .and_then(|()| game.take_turn(cg.team, Seat::Computer));
+            if let Err(err) = turn {
+                return refuse(err);
+            }
+            let res = computer_move(&mut game.board, searched, cg.team, pos, format);
+            game.finished = game.board.check_winner();
+            game.publish(res)
+        })
+        .unwrap_or_else(game_not_found)
+}
+
+/// Takes back the last move of an unfinished game, for the player who made it.
+#[handler]
+fn undo_game(
+    Path(id): Path<Uuid>,
+    Player(player): Player,
+    format: Format,
+    Data(games): Data<&Arc<Games>>,
+) -> Response {
+    games
+        .with_game(id, |game| {
+            if let Err(err) = game.check_undo(&Seat::Player(player)) {
+                return refuse(err);
+            }
+            let res = undo(&mut game.board, format);
+            game.publish(res)
+        })
+        .unwrap_or_else(game_not_found)
+}
+
+#[handler]
+fn replay_game(Path(id): Path<Uuid>, format: Format, Data(games): Data<&Arc<Games>>) -> Response {
+    games
+        .with(id, |board| replay(board, format))
+        .unwrap_or_else(game_not_found)
+}
+
+#[handler]
+fn export_game(Path(id): Path<Uuid>, Data(games): Data<&Arc<Games>>) -> Response {
+    games
+        .with(id, |board| export(board))
+        .unwrap_or_else(game_not_found)
+}
+
+#[derive(Serialize)]
+struct SeatsState {
+    cookie: bool,
+    milk: bool,
+}
+
+#[derive(Serialize)]
+struct GameState {
+    whose_turn: Option<Token>,
+    legal_columns: Vec<usize>,
+    seats: SeatsState,
+}
+
+#[handler]
+fn game_state(Path(id): Path<Uuid>, Data(games): Data<&Arc<Games>>) -> Response {
+    games
+        .with_game(id, |game| {
+            Json(GameState {
+                whose_turn: game.board.whose_turn(),
+                legal_columns: game.board.legal_columns(),
+                seats: SeatsState {
+                    cookie: game.seats.cookie.is_some(),
+                    milk: game.seats.milk.is_some(),
+                },
+            })
+            .into_response()
+        })
+        .unwrap_or_else(game_not_found)
+}
+
+/// Fills a game with a random board, for players holding one of its seats.
+#[handler]
+fn random_game(
+    Path(id): Path<Uuid>,
+    Query(query): Query<RandomBoard>,
+    Player(player): Player,
+    format: Format,
+    Data(games): Data<&Arc<Games>>,
+) -> Response {
+    games
+        .with_game(id, |game| {
+            if let Err(err) = game.check_player(&Seat::Player(player)) {
+                return refuse(err);
+            }
+            let res = randomize(&mut game.board, &query, format);
+            game.publish(res)
+        })
+        .unwrap_or_else(game_not_found)
+}
+
+/// A move sent over the socket, like `{"team": "cookie", "column": 3}`.
+#[derive(Deserialize, Debug)]
+struct SocketMove {
+    team: Token,
+    column: usize,
+}
+
+fn error_message(err: &str) -> Message {
+    Message::Text(serde_json::json!({ "error": err }).to_string())
+}
+
+/// Sends the board now and after every change, and plays the moves received. Changes reach
+/// every watcher through `updates`, while refused moves are answered with `{"error": ...}`.
+async fn watch(
+    mut socket: WebSocketStream,
+    current: String,
+    mut updates: broadcast::Receiver<String>,
+    mut play: impl FnMut(SocketMove) -> Result<(), String> + Send,
+) {
+    if socket.send(Message::Text(current)).await.is_err() {
+        return;
+    }
+    loop {
+        let reply = tokio::select! {
+            update = updates.recv() => match update {
+                Ok(board) => Message::Text(board),
+                // Watchers that fell behind catch up with the next change.
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            message = socket.next() => match message {
+                Some(Ok(Message::Text(text))) => {
+                    let played = serde_json::from_str(&text)
+                        .map_err(|err| err.to_string())
+                        .and_then(&mut play);
+                    match played {
+                        Ok(()) => continue,
+                        Err(err) => error_message(&err),
+                    }
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+        };
+        if socket.send(reply).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Watches the free play board over WebSocket, where anyone may move.
+#[handler]
+fn watch_board(
+    ws: WebSocket,
+    Data(board): Data<&Board>,
+    Data(updates): Data<&Updates>,
+) -> Response {
+    let current = board_message(&board.lock().unwrap());
+    let (board, updates) = (board.clone(), updates.clone());
+    let receiver = updates.0.subscribe();
+    ws.on_upgrade(move |socket| {
+        watch(socket, current, receiver, move |m| {
+            let mut board = board.lock().unwrap();
+            board.check_move(m.team, m.column).map_err(|(_, err)| err)?;
+            board.place(m.column - 1, m.team);
+            updates.send(&board);
+            Ok(())
+        })
+    })
+    .into_response()
+}
+
+/// Watches a game over WebSocket, moving as the player who opened the socket.
+#[handler]
+fn watch_game(
+    ws: WebSocket,
+    Path(id): Path<Uuid>,
+    Player(player): Player,
+    Data(games): Data<&Arc<Games>>,
+) -> Response {
+    let Some((current, receiver)) = games.with_game(id, |game| {
+        (board_message(&game.board), game.updates.0.subscribe())
+    }) else {
+        return game_not_found();
+    };
+    let games = games.clone();
+    ws.on_upgrade(move |socket| {
+        watch(socket, current, receiver, move |m| {
+            games
+                .with_game(id, |game| {
+                    game.play(m.team, m.column, Seat::Player(player.clone()))
+                        .map_err(|(_, err)| err)?;
+                    game.changed();
+                    Ok(())
+                })
+                .unwrap_or_else(|| Err("Game not found".to_string()))
+        })
+    })
+    .into_response()
+}
+
+/// Games are saved to the database unless `GAMES_STORE` is `memory`.
+pub(crate) fn route(pool: sqlx::PgPool) -> impl Endpoint {
+    let games = match std::env::var("GAMES_STORE").as_deref() {
+        Ok("memory") => Arc::new(Games::default()),
+        _ => Games::persist(pool),
+    };
+    Route::new()
+        .at("/board", get(show_board))
+        .at("/reset", post(reset_board))
+        .at("/place/:team/:position", post(place_board))
+        .at("/random-board", get(random_board))
+        .at("/ai/:team", post(computer_board))
+        .at("/undo", post(undo_board))
+        .at("/replay", get(replay_board))
+        .at("/export", get(export_board))
+        .at("/import", post(import_board))
+        .at("/ws", get(watch_board))
+        .at("/games", get(list_games).post(create_game))
+        .at("/games/import", post(import_game))
+        .at("/leaderboard", get(leaderboard))
+        .at("/games/:id", delete(delete_game))
+        .at("/games/:id/board", get(show_game))
+        .at("/games/:id/state", get(game_state))
+        .at("/games/:id/reset", post(reset_game))
+        .at("/games/:id/place/:team/:column", post(place_game))
+        .at("/games/:id/random-board", get(random_game))
+        .at("/games/:id/ai/:team", post(computer_game))
+        .at("/games/:id/undo", post(undo_game))
+        .at("/games/:id/replay", get(replay_game))
+        .at("/games/:id/export", get(export_game))
+        .at("/games/:id/ws", get(watch_game))
+        .with(AddData::new(Arc::new(Mutex::new(BoardImpl::new()))))
+        .with(AddData::new(Updates::default()))
+        .with(AddData::new(games))
+        .with(AddData::new(PlayerKey::from_env()))
+        .with(CookieJarManager::new())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_vertical_win() {
+        let mut board = BoardImpl::new();
+
+        board.place(0, Token::Cookie);
+        board.place(0, Token::Cookie);
+        board.place(0, Token::Cookie);
+        board.place(0, Token::Cookie);
+
+        assert_eq!(board.check_winner(), Some(Token::Cookie));
+    }
+
+    #[test]
+    fn test_full_column() {
+        let mut board = BoardImpl::with_size(BoardSize {
+            width: 2,
+            height: 2,
+            connect: 2,
+        });
+        assert!(board.place(0, Token::Cookie));
+        assert!(board.place(0, Token::Milk));
+        assert!(!board.place(0, Token::Cookie));
+        assert_eq!(board.legal_columns(), vec![2]);
+    }
+
+    #[test]
+    fn test_connect_four_diagonals() {
+        let mut board = BoardImpl::with_size(BoardSize {
+            width: 7,
+            height: 6,
+            connect: 4,
+        });
+
+        for (column, below) in [(3, 0), (4, 1), (5, 2), (6, 3)] {
+            for _ in 0..below {
+                board.place(column, Token::Milk);
+            }
+            board.place(column, Token::Cookie);
+        }
+        assert_eq!(board.check_winner(), Some(Token::Cookie));
+
+        board.reset(board.size());
+        for (column, below) in [(0, 3), (1, 2), (2, 1), (3, 0)] {
+            for _ in 0..below {
+                board.place(column, Token::Cookie);
+            }
+            board.place(column, Token::Milk);
+        }
+        assert_eq!(board.check_winner(), Some(Token::Milk));
+    }
+
+    #[test]
+    fn test_print_any_size() {
+        let mut board = BoardImpl::with_size(BoardSize {
+            width: 3,
+            height: 2,
+            connect: 2,
+        });
+        board.place(1, Token::Milk);
+        assert_eq!(board.print(), "⬜⬛⬛⬛⬜\n⬜⬛🥛⬛⬜\n⬜⬜⬜⬜⬜\n");
+        board.place(1, Token::Milk);
+        assert_eq!(
+            board.print(),
+            "⬜⬛🥛⬛⬜\n⬜⬛🥛⬛⬜\n⬜⬜⬜⬜⬜\n🥛 wins!\n"
+        );
+    }
+
+    #[test]
+    fn test_idle_games_expire() {
+        let games = Games::default();
+        let idle = games.create(BoardImpl::new()).id;
+        let active = games.create(BoardImpl::new()).id;
+        games
+            .games
+            .lock()
+            .unwrap()
+            .get_mut(&idle)
+            .unwrap()
+            .last_access = Instant::now() - GAME_TTL - Duration::from_secs(1);
+
+        assert!(games.with(idle, |_| ()).is_none());
+        assert!(games.with(active, |_| ()).is_some());
+        assert_eq!(games.list().len(), 1);
+    }
+
+    #[test]
+    fn test_player_key() {
+        let token = jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &serde_json::json!({"sub": "bob"}),
+            &jsonwebtoken::EncodingKey::from_secret(b"player secret"),
+        )
+        .unwrap();
+
+        let key = PlayerKey(Some(jsonwebtoken::DecodingKey::from_secret(
+            b"player secret",
+        )));
+        assert!(matches!(key.verify(&token), Some(Player(sub)) if sub == "bob"));
+        let other = PlayerKey(Some(jsonwebtoken::DecodingKey::from_secret(b"secret")));
+        assert!(other.verify(&token).is_none());
+        assert!(PlayerKey::default().verify(&token).is_none());
+    }
+
+    #[test]
+    fn test_pending_changes() {
+        let pending = Pending::default();
+        let (first, second) = (Uuid::from_u128(1), Uuid::from_u128(2));
+        let mut game = Game::new(BoardImpl::new(), Seats::default());
+        pending.push(first, Change::Save(SavedGame::new(first, &game)));
+        game.board.place(0, Token::Cookie);
+        pending.push(first, Change::Save(SavedGame::new(first, &game)));
+        pending.push(second, Change::Delete(second));
+
+        let (changes, results) = pending.take();
+        assert_eq!(changes.len(), 2);
+        assert!(changes
+            .iter()
+            .any(|change| matches!(change, Change::Save(saved) if saved.moves.len() == 1)));
+        assert!(results.is_empty());
+        assert!(pending.take().0.is_empty());
+    }
+
+    #[test]
+    fn test_game_results() {
+        let games = Games {
+            pending: Some(Arc::default()),
+            ..Games::default()
+        };
+        let id = games
+            .create(BoardImpl::with_size(BoardSize {
+                width: 2,
+                height: 2,
+                connect: 2,
+            }))
+            .id;
+        for (team, column) in [(Token::Cookie, 1), (Token::Milk, 2), (Token::Cookie, 1)] {
+            games
+                .with_game(id, |game| {
+                    game.play(team, column, Seat::Player(team.name().to_string()))?;
+                    game.changed();
+                    Ok::<_, (StatusCode, String)>(())
+                })
+                .unwrap()
+                .unwrap();
+        }
+        games.with_game(id, |game| {
+            game.board.reset(game.board.size());
+            game.changed();
+        });
+
+        let (_, results) = games.pending.as_ref().unwrap().take();
+        assert_eq!(
+            results,
+            vec![GameResult {
+                game_id: id,
+                outcome: "cookie",
+                cookie: Some(handle("cookie")),
+                milk: Some(handle("milk")),
+            }]
+        );
+        assert_eq!(handle("cookie").len(), 16);
+        assert_ne!(handle("cookie"), handle("milk"));
+    }
+
+    #[test]
+    fn test_saved_game() {
+        let mut board = BoardImpl::new();
+        board.place(1, Token::Cookie);
+        board.place(1, Token::Milk);
+        let mut game = Game::new(board, Seats::default());
+        game.seats.cookie = Some(Seat::Player("alice".to_string()));
+        game.seats.milk = Some(Seat::Computer);
+
+        let saved = SavedGame::new(Uuid::nil(), &game);
+        assert_eq!(saved.grid, ".........m...c..");
+        assert_eq!(saved.outcome, None);
+        assert_eq!(saved.moves.len(), 2);
+        assert_eq!(
+            saved.seats,
+            vec![
+                (Token::Cookie, Some("alice".to_string())),
+                (Token::Milk, None)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_play_random() {
+        for seed in 0..200 {
+            let mut board = connect_four();
+            board.play_random(seed, 1.0, true);
+            assert!(board.check_winner().is_none());
+            // Every token rests on the bottom or on another token.
+            for row in 0..board.height - 1 {
+                for column in 0..board.width {
+                    if board.inner[row][column] != Token::Empty {
+                        assert_ne!(board.inner[row + 1][column], Token::Empty);
+                    }
+                }
+            }
+            let replayed = BoardImpl::import(&board.export().unwrap()).unwrap();
+            assert_eq!(replayed.print(), board.print());
+        }
+
+        let mut board = connect_four();
+        board.play_random(7, 0.5, false);
+        let first = board.print();
+        assert!(board.moves.len() == 21 || board.check_winner().is_some());
+        board.play_random(7, 0.5, false);
+        assert_eq!(board.print(), first);
+        board.play_random(7, 0.0, false);
+        assert_eq!(board.print(), connect_four().print());
+    }
+
+    /// The win checks and heuristic looking at every cell, to compare the bitboard against.
+    impl BoardImpl {
+        fn get(&self, row: isize, column: isize) -> Option<Token> {
+            let row = self.inner.get(usize::try_from(row).ok()?)?;
+            row.get(usize::try_from(column).ok()?).copied()
+        }
+
+        /// Whether `connect` equal tokens start at (`row`, `column`) going in `direction`.
+        fn is_line(&self, row: usize, column: usize, (dr, dc): (isize, isize)) -> bool {
+            let token = self.inner[row][column];
+            if matches!(token, Token::Empty) {
+                return false;
+            }
+            let (row, column) = (row.cast_signed(), column.cast_signed());
+            (1..self.connect.cast_signed())
+                .all(|i| self.get(row + dr * i, column + dc * i) == Some(token))
+        }
+
+        fn scan_winner(&self) -> Option<Token> {
+            for direction in DIRECTIONS {
+                for row in 0..self.height {
+                    for column in 0..self.width {
+                        if self.is_line(row, column, direction) {
+                            return Some(self.inner[row][column]);
+                        }
+                    }
+                }
+            }
+            self.inner
+                .iter()
+                .flatten()
+                .all(|&token| token != Token::Empty)
+                .then_some(Token::Empty)
+        }
+
+        fn scan_wins_at(&self, row: usize, column: usize) -> bool {
+            let token = self.inner[row][column];
+            let (row, column) = (row.cast_signed(), column.cast_signed());
+            let run = |dr: isize, dc: isize| {
+                (1..)
+                    .take_while(|&i| self.get(row + dr * i, column + dc * i) == Some(token))
+                    .count()
+            };
+            !matches!(token, Token::Empty)
+                && DIRECTIONS
+                    .iter()
+                    .any(|&(dr, dc)| 1 + run(dr, dc) + run(-dr, -dc) >= self.connect)
+        }
+
+        fn scan_evaluate(&self, team: Token) -> i32 {
+            let (mut mine, mut theirs) = (0, 0);
+            for (dr, dc) in DIRECTIONS {
+                for row in 0..self.height.cast_signed() {
+                    for column in 0..self.width.cast_signed() {
+                        let (mut own, mut other) = (0, 0);
+                        for i in 0..self.connect.cast_signed() {
+                            match self.get(row + dr * i, column + dc * i) {
+                                None => {
+                                    (own, other) = (0, 0);
+                                    break;
+                                }
+                                Some(Token::Empty) => {}
+                                Some(token) if token == team => own += 1,
+                                Some(_) => other += 1,
+                            }
+                        }
+                        if other == 0 {
+                            mine += own * own;
+                        } else if own == 0 {
+                            theirs += other * other;
+                        }
+                    }
+                }
+            }
+            mine - theirs
+        }
+    }
+
+    #[test]
+    fn test_bitboard_matches_scanner() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(40);
+        for _ in 0..3000 {
+            let width = rng.gen_range(1..=MAX_SIDE);
+            let height = rng.gen_range(1..=MAX_SIDE);
+            let connect = rng.gen_range(2..=width.max(height).max(2));
+            let mut board = BoardImpl::with_size(BoardSize {
+                width,
+                height,
+                connect,
+            });
+            let density = rng.gen::<f64>();
+            for row in 0..height {
+                for column in 0..width {
+                    if rng.gen_bool(density) {
+                        let token = if rng.gen() {
+                            Token::Cookie
+                        } else {
+                            Token::Milk
+                        };
+                        board.set(row, column, token);
+                    }
+                }
+            }
+
+            assert_eq!(
+                board.check_winner(),
+                board.scan_winner(),
+                "\n{}",
+                board.print()
+            );
+            assert_eq!(
+                board.evaluate(Token::Cookie),
+                board.scan_evaluate(Token::Cookie)
+            );
+            for row in 0..height {
+                for column in 0..width {
+                    assert_eq!(board.wins_at(row, column), board.scan_wins_at(row, column));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_largest_board() {
+        let size = BoardSize {
+            width: MAX_SIDE,
+            height: MAX_SIDE,
+            connect: 5,
+        };
+        // The bottom row straddles two words of the bitboard.
+        let mut board = BoardImpl::with_size(size);
+        for column in 0..5 {
+            board.place(column, Token::Milk);
+        }
+        assert_eq!(board.check_winner(), Some(Token::Milk));
+        assert!(board.wins_at(MAX_SIDE - 1, 0));
+
+        let mut board = BoardImpl::with_size(size);
+        for _ in 0..MAX_SIDE {
+            for column in 0..MAX_SIDE {
+                board.place(column, Token::Cookie);
+            }
+        }
+        assert_eq!(board.bits.cookie.count_ones(), 256);
+        assert_eq!(board.check_winner(), Some(Token::Cookie));
+    }
+
+    fn connect_four() -> BoardImpl {
+        BoardImpl::with_size(BoardSize {
+            width: 7,
+            height: 6,
+            connect: 4,
+        })
+    }
+
+    #[test]
+    fn test_computer_wins_and_blocks() {
+        for difficulty in [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard] {
+            let mut board = connect_four();
+            for column in [0, 1, 2] {
+                board.place(column, Token::Milk);
+                board.place(6, Token::Cookie);
+            }
+            // Cookie wins in column 6 rather than blocking milk.
+            assert_eq!(board.best_move(Token::Cookie, difficulty.depth()), Some(6));
+            // Milk takes its own win.
+            assert_eq!(board.best_move(Token::Milk, difficulty.depth()), Some(3));
+        }
+
+        let mut board = connect_four();
+        for column in [2, 3, 4] {
+            board.place(column, Token::Milk);
+        }
+        board.place(2, Token::Cookie);
+        board.place(3, Token::Cookie);
+        // Milk threatens both ends, so cookie must block one of them.
+        let pos = board.best_move(Token::Cookie, Difficulty::Medium.depth());
+        assert!(matches!(pos, Some(1 | 5)));
+    }
+
+    #[test]
+    fn test_search_budget() {
+        let mut board = connect_four();
+        board.place(3, Token::Cookie);
+        let before = board.print();
+        let mut budget = 100;
+        assert!(board.best_moves(Token::Milk, 7, &mut budget).is_none());
+        // Running out of budget takes the searched tokens back.
+        assert_eq!(board.print(), before);
+        // The hardest search fits on the usual board.
+        let mut budget = SEARCH_BUDGET;
+        let depth = Difficulty::Hard.depth();
+        assert!(board.best_moves(Token::Milk, depth, &mut budget).is_some());
+    }
+
+    #[test]
+    fn test_computer_is_deterministic() {
+        let play = || {
+            let mut board = connect_four();
+            let mut team = Token::Cookie;
+            let mut moves = vec![];
+            while board.check_winner().is_none() {
+                let pos = board.best_move(team, Difficulty::Easy.depth()).unwrap();
+                board.place(pos, team);
+                moves.push(pos);
+                team = team.opponent();
+            }
+            moves
+        };
+        assert_eq!(play(), play());
+    }
+
+    #[test]
+    fn test_undo_and_replay() {
+        let mut board = connect_four();
+        board.place(3, Token::Cookie);
+        board.place(3, Token::Milk);
+        board.place(2, Token::Cookie);
+
+        let states = board.replay().unwrap();
+        assert_eq!(states.len(), 4);
+        assert_eq!(states[0].print(), connect_four().print());
+        assert_eq!(states[3].print(), board.print());
+
+        assert_eq!(
+            board.undo(),
+            Some(Move {
+                team: Token::Cookie,
+                column: 2
+            })
+        );
+        assert_eq!(board.print(), states[2].print());
+        board.undo();
+        board.undo();
+        assert_eq!(board.undo(), None);
+        assert_eq!(board.print(), connect_four().print());
+    }
+
+    #[test]
+    fn test_export_import() {
+        let mut board = connect_four();
+        board.place(3, Token::Cookie);
+        board.place(3, Token::Milk);
+        board.place(2, Token::Cookie);
+        let notation = board.export().unwrap();
+        assert_eq!(notation, "7x6c4:c4m4c3");
+        let imported = BoardImpl::import(&notation).unwrap();
+        assert_eq!(imported.print(), board.print());
+        assert_eq!(imported.moves, board.moves);
+
+        assert!(BoardImpl::import("4x4c4:c1c1c1c1m2").is_err());
+        assert!(BoardImpl::import("4x4c4:c1c1c1c1c1").is_err());
+        assert!(BoardImpl::import("4x4c4:c5").is_err());
+        assert!(BoardImpl::import("4x4c4:x1").is_err());
+        assert!(BoardImpl::import("4x4c4:c").is_err());
+        assert!(BoardImpl::import("4x4").is_err());
+        assert!(BoardImpl::import("4x4c4:").is_ok());
+
+        board.randomized = true;
+        assert_eq!(board.export(), None);
+        assert!(board.replay().is_none());
     }
 }
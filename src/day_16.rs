@@ -1,12 +1,6 @@
 use poem_openapi::payload::Json;
 use jsonwebtoken::errors::ErrorKind;
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
-struct Claims {
-    sub: String,
-    company: String,
-}
-
 #[derive(Debug, poem_openapi::ApiResponse)]
 #[oai(header(name = "Set-Cookie", ty = "String"))]
 enum WrapResponse {
@@ -1,21 +1,547 @@
 use poem::http;
-use poem::web::Multipart;
-use poem_openapi::param::Path;
-use poem_openapi::payload::Html;
+use poem::web::{Field, Multipart};
+use poem_openapi::param::{Header, Path};
+use poem_openapi::payload::{Html, Json, PlainText};
+use poem_openapi::Object;
+use serde::de::DeserializeOwned;
 use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use tokio::io::AsyncReadExt;
 
+/// The most bytes of lockfiles read from one request, unless `LOCKFILE_SIZE_LIMIT` is set.
+const DEFAULT_UPLOAD_LIMIT: u64 = 1024 * 1024;
+
+#[derive(Deserialize)]
+struct LockfileParsed<P = Packages> {
+    package: Vec<P>,
+}
+
+/// All that `/23/lockfile` needs of a package, so that it takes packages missing the rest.
 #[derive(Deserialize)]
-struct LockfileParsed {
-    package: Vec<Packages>,
+struct Checksum {
+    #[serde(default)]
+    checksum: Option<String>,
 }
 
 #[derive(Deserialize)]
 struct Packages {
+    name: String,
+    version: String,
+    #[serde(default)]
+    source: Option<String>,
     #[serde(default)]
     checksum: Option<String>,
+    /// Each as `name`, `name version` or `name version (source)`, as short as is unambiguous.
+    #[serde(default)]
+    dependencies: Vec<String>,
+}
+
+/// The sources of packages from crates.io, through the git and the sparse index.
+const CRATES_IO: &[&str] = &[
+    "registry+https://github.com/rust-lang/crates.io-index",
+    "sparse+https://index.crates.io/",
+];
+
+impl Packages {
+    fn id(&self) -> String {
+        format!("{} {}", self.name, self.version)
+    }
+}
+
+impl LockfileParsed {
+    /// The package a `dependencies` entry refers to.
+    fn resolve(&self, dependency: &str) -> Option<&Packages> {
+        let mut parts = dependency.splitn(3, ' ');
+        let name = parts.next()?;
+        let version = parts.next();
+        let source = parts
+            .next()
+            .map(|source| source.trim_start_matches('(').trim_end_matches(')'));
+        self.package.iter().find(|p| {
+            p.name == name
+                && version.is_none_or(|version| p.version == version)
+                && source.is_none_or(|source| p.source.as_deref() == Some(source))
+        })
+    }
+
+    fn graph(&self) -> Result<Graph, String> {
+        let nodes = self
+            .package
+            .iter()
+            .map(|p| GraphNode {
+                id: p.id(),
+                name: p.name.clone(),
+                version: p.version.clone(),
+                source: p.source.clone(),
+            })
+            .collect();
+        let mut edges = Vec::new();
+        for package in &self.package {
+            for dependency in &package.dependencies {
+                let to = self.resolve(dependency).ok_or_else(|| {
+                    format!(
+                        "{} depends on {dependency}, which is not locked",
+                        package.id()
+                    )
+                })?;
+                edges.push(GraphEdge {
+                    from: package.id(),
+                    to: to.id(),
+                });
+            }
+        }
+        Ok(Graph { nodes, edges })
+    }
+}
+
+#[derive(Object)]
+struct GraphNode {
+    /// `name version`, unique unless the same version comes from several sources.
+    id: String,
+    name: String,
+    version: String,
+    source: Option<String>,
+}
+
+#[derive(Object)]
+struct GraphEdge {
+    from: String,
+    to: String,
 }
 
-pub struct Api;
+#[derive(Object)]
+struct Graph {
+    nodes: Vec<GraphNode>,
+    edges: Vec<GraphEdge>,
+}
+
+impl Graph {
+    /// Adds the nodes and edges of another graph that are not in this one yet.
+    fn merge(&mut self, other: Graph) {
+        for node in other.nodes {
+            if !self.nodes.iter().any(|n| n.id == node.id) {
+                self.nodes.push(node);
+            }
+        }
+        for edge in other.edges {
+            if !self
+                .edges
+                .iter()
+                .any(|e| e.from == edge.from && e.to == edge.to)
+            {
+                self.edges.push(edge);
+            }
+        }
+    }
+
+    /// The graph in Graphviz's DOT language.
+    fn dot(&self) -> String {
+        let quote = |id: &str| format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut dot = "digraph lockfile {\n".to_string();
+        for node in &self.nodes {
+            writeln!(dot, "    {};", quote(&node.id)).unwrap();
+        }
+        for edge in &self.edges {
+            writeln!(dot, "    {} -> {};", quote(&edge.from), quote(&edge.to)).unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[derive(poem_openapi::ApiResponse)]
+enum GraphResponse {
+    #[oai(status = 200)]
+    Json(Json<Graph>),
+    #[oai(status = 200, content_type = "text/vnd.graphviz")]
+    Dot(PlainText<String>),
+}
+
+/// A crate locked at more than one version.
+#[derive(Object)]
+struct Duplicate {
+    name: String,
+    versions: Vec<String>,
+}
+
+/// A package that does not come from crates.io.
+#[derive(Object)]
+struct ForeignPackage {
+    name: String,
+    version: String,
+    source: String,
+}
+
+/// An advisory from a RustSec advisory database.
+struct Advisory {
+    id: String,
+    package: String,
+    title: String,
+    date: String,
+    url: Option<String>,
+    patched: Vec<semver::VersionReq>,
+    unaffected: Vec<semver::VersionReq>,
+}
+
+#[derive(Deserialize)]
+struct AdvisoryFile {
+    advisory: AdvisoryHeader,
+    #[serde(default)]
+    versions: AdvisoryVersions,
+}
+
+#[derive(Deserialize)]
+struct AdvisoryHeader {
+    id: String,
+    package: String,
+    date: String,
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    withdrawn: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+struct AdvisoryVersions {
+    #[serde(default)]
+    patched: Vec<String>,
+    #[serde(default)]
+    unaffected: Vec<String>,
+}
+
+impl Advisory {
+    /// Reads an advisory written as Markdown, with its TOML front matter in a ```toml block and
+    /// its title as the first heading. Withdrawn advisories and other Markdown give `None`.
+    fn parse(text: &str) -> Result<Option<Self>, String> {
+        let Some((_, rest)) = text.split_once("```toml\n") else {
+            return Ok(None);
+        };
+        let (front, body) = rest
+            .split_once("\n```")
+            .ok_or("unterminated ```toml block")?;
+        let file: AdvisoryFile = toml::from_str(front).map_err(|err| err.to_string())?;
+        if file.advisory.withdrawn.is_some() {
+            return Ok(None);
+        }
+        let requirements = |reqs: Vec<String>| -> Result<Vec<semver::VersionReq>, String> {
+            reqs.iter()
+                .map(|req| semver::VersionReq::parse(req).map_err(|err| format!("{req}: {err}")))
+                .collect()
+        };
+        let title = body
+            .lines()
+            .find_map(|line| line.strip_prefix("# "))
+            .unwrap_or_default();
+        Ok(Some(Advisory {
+            title: title.trim().to_string(),
+            date: file.advisory.date,
+            url: file.advisory.url,
+            patched: requirements(file.versions.patched)?,
+            unaffected: requirements(file.versions.unaffected)?,
+            id: file.advisory.id,
+            package: file.advisory.package,
+        }))
+    }
+
+    fn affects(&self, version: &semver::Version) -> bool {
+        !self
+            .patched
+            .iter()
+            .chain(&self.unaffected)
+            .any(|req| req.matches(version))
+    }
+}
+
+/// The advisories by the name of the package they are about.
+#[derive(Default)]
+struct Advisories(HashMap<String, Vec<Advisory>>);
+
+impl Advisories {
+    /// Every advisory in the database checked out at `dir`.
+    fn load(dir: &std::path::Path) -> Self {
+        let mut advisories = Self::default();
+        advisories.load_dir(dir);
+        advisories
+    }
+
+    fn load_dir(&mut self, dir: &std::path::Path) {
+        let entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) => return eprintln!("Failed to read {}: {err}", dir.display()),
+        };
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path
+                .file_name()
+                .is_some_and(|n| n.to_string_lossy().starts_with('.'))
+            {
+                continue;
+            }
+            if path.is_dir() {
+                self.load_dir(&path);
+            } else if path.extension().is_some_and(|e| e == "md") {
+                let advisory = std::fs::read_to_string(&path)
+                    .map_err(|err| err.to_string())
+                    .and_then(|text| Advisory::parse(&text));
+                match advisory {
+                    Ok(Some(advisory)) => self.add(advisory),
+                    Ok(None) => {}
+                    Err(err) => eprintln!("Invalid advisory {}: {err}", path.display()),
+                }
+            }
+        }
+    }
+
+    fn add(&mut self, advisory: Advisory) {
+        let advisories = self.0.entry(advisory.package.clone()).or_default();
+        advisories.push(advisory);
+        advisories.sort_by(|a, b| a.id.cmp(&b.id));
+    }
+
+    /// The advisories affecting the locked packages, in the order of the lockfile.
+    fn check(&self, lockfile: &LockfileParsed) -> Vec<AdvisoryMatch> {
+        let mut matches = Vec::new();
+        for package in &lockfile.package {
+            let Ok(version) = semver::Version::parse(&package.version) else {
+                continue;
+            };
+            let advisories = self.0.get(&package.name).into_iter().flatten();
+            for advisory in advisories.filter(|a| a.affects(&version)) {
+                matches.push(AdvisoryMatch {
+                    package: package.name.clone(),
+                    version: package.version.clone(),
+                    id: advisory.id.clone(),
+                    title: advisory.title.clone(),
+                    date: advisory.date.clone(),
+                    url: advisory.url.clone(),
+                    patched: advisory.patched.iter().map(ToString::to_string).collect(),
+                });
+            }
+        }
+        matches
+    }
+}
+
+#[derive(Object)]
+struct AdvisoryMatch {
+    package: String,
+    version: String,
+    id: String,
+    title: String,
+    date: String,
+    url: Option<String>,
+    /// The versions with a fix.
+    patched: Vec<String>,
+}
+
+fn advisories_html(matches: &[AdvisoryMatch]) -> String {
+    if matches.is_empty() {
+        return r#"<div id="advisories" class="clean">No known advisories</div>"#.to_string();
+    }
+    let escape = |text: &str| askama_escape::escape(text, askama_escape::Html).to_string();
+    let mut html = r#"<ul id="advisories">"#.to_string();
+    for m in matches {
+        let id = match &m.url {
+            Some(url) => format!(r#"<a href="{}">{}</a>"#, escape(url), escape(&m.id)),
+            None => escape(&m.id),
+        };
+        write!(
+            html,
+            r#"<li class="advisory">{id} <span class="package">{} {}</span> {}</li>"#,
+            escape(&m.package),
+            escape(&m.version),
+            escape(&m.title),
+        )
+        .unwrap();
+    }
+    html.push_str("</ul>");
+    html
+}
+
+#[derive(Object)]
+struct PackageVersion {
+    name: String,
+    version: String,
+}
+
+#[derive(Object)]
+struct VersionChange {
+    name: String,
+    from: String,
+    to: String,
+}
+
+/// The same version of a package with another checksum, or one only on one side.
+#[derive(Object)]
+struct ChecksumChange {
+    name: String,
+    version: String,
+    from: Option<String>,
+    to: Option<String>,
+}
+
+#[derive(Object)]
+struct LockfileDiff {
+    added: Vec<PackageVersion>,
+    removed: Vec<PackageVersion>,
+    changed: Vec<VersionChange>,
+    checksums: Vec<ChecksumChange>,
+}
+
+impl LockfileDiff {
+    /// Compares the versions of each package by name. Where versions were both removed and
+    /// added, they are paired up from the lowest as changes, and the rest are left unpaired.
+    fn new(old: &LockfileParsed, new: &LockfileParsed) -> Self {
+        let by_name = |lockfile: &LockfileParsed| {
+            let mut packages = BTreeMap::<String, BTreeMap<String, Option<String>>>::new();
+            for p in &lockfile.package {
+                let versions = packages.entry(p.name.clone()).or_default();
+                versions.insert(p.version.clone(), p.checksum.clone());
+            }
+            packages
+        };
+        let (old, mut new) = (by_name(old), by_name(new));
+        let mut diff = LockfileDiff {
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+            checksums: Vec::new(),
+        };
+        let mut names: Vec<String> = old.keys().chain(new.keys()).cloned().collect();
+        names.sort();
+        names.dedup();
+        for name in names {
+            let old = old.get(&name).cloned().unwrap_or_default();
+            let new = new.remove(&name).unwrap_or_default();
+            let only = |a: &BTreeMap<String, Option<String>>, b: &BTreeMap<String, _>| {
+                let mut versions: Vec<String> =
+                    a.keys().filter(|v| !b.contains_key(*v)).cloned().collect();
+                versions.sort_by_cached_key(|v| semver::Version::parse(v).ok());
+                versions
+            };
+            let (mut removed, mut added) =
+                (only(&old, &new).into_iter(), only(&new, &old).into_iter());
+            loop {
+                match (removed.next(), added.next()) {
+                    (Some(from), Some(to)) => diff.changed.push(VersionChange {
+                        name: name.clone(),
+                        from,
+                        to,
+                    }),
+                    (Some(version), None) => diff.removed.push(PackageVersion {
+                        name: name.clone(),
+                        version,
+                    }),
+                    (None, Some(version)) => diff.added.push(PackageVersion {
+                        name: name.clone(),
+                        version,
+                    }),
+                    (None, None) => break,
+                }
+            }
+            for (version, from) in &old {
+                match new.get(version) {
+                    Some(to) if to != from => diff.checksums.push(ChecksumChange {
+                        name: name.clone(),
+                        version: version.clone(),
+                        from: from.clone(),
+                        to: to.clone(),
+                    }),
+                    _ => {}
+                }
+            }
+        }
+        diff
+    }
+
+    fn html(&self) -> String {
+        let escape = |text: &str| askama_escape::escape(text, askama_escape::Html).to_string();
+        let mut rows = Vec::new();
+        for p in &self.added {
+            rows.push(("added", &p.name, "", p.version.as_str()));
+        }
+        for p in &self.removed {
+            rows.push(("removed", &p.name, p.version.as_str(), ""));
+        }
+        for c in &self.changed {
+            rows.push(("changed", &c.name, c.from.as_str(), c.to.as_str()));
+        }
+        let mut html = concat!(
+            r#"<table id="lockfile-diff"><thead><tr>"#,
+            "<th>Package</th><th>Change</th><th>Old</th><th>New</th>",
+            "</tr></thead><tbody>",
+        )
+        .to_string();
+        for (change, name, old, new) in rows {
+            write!(
+                html,
+                r#"<tr class="{change}"><td>{}</td><td>{change}</td><td>{}</td><td>{}</td></tr>"#,
+                escape(name),
+                escape(old),
+                escape(new),
+            )
+            .unwrap();
+        }
+        for c in &self.checksums {
+            write!(
+                html,
+                r#"<tr class="checksum"><td>{} {}</td><td>checksum</td><td>{}</td><td>{}</td></tr>"#,
+                escape(&c.name),
+                escape(&c.version),
+                escape(c.from.as_deref().unwrap_or_default()),
+                escape(c.to.as_deref().unwrap_or_default()),
+            )
+            .unwrap();
+        }
+        html.push_str("</tbody></table>");
+        html
+    }
+}
+
+#[derive(poem_openapi::ApiResponse)]
+enum DiffResponse {
+    #[oai(status = 200)]
+    Json(Json<LockfileDiff>),
+    #[oai(status = 200)]
+    Html(Html<String>),
+}
+
+#[derive(poem_openapi::ApiResponse)]
+enum AdvisoryResponse {
+    #[oai(status = 200)]
+    Json(Json<Vec<AdvisoryMatch>>),
+    #[oai(status = 200)]
+    Html(Html<String>),
+}
+
+pub struct Api {
+    advisories: Advisories,
+    upload_limit: u64,
+}
+
+impl Api {
+    /// Configured by `ADVISORY_DB`, the directory of an advisory database, and
+    /// `LOCKFILE_SIZE_LIMIT`, in bytes.
+    pub fn new() -> Self {
+        let advisory_db = std::env::var_os("ADVISORY_DB").map(std::path::PathBuf::from);
+        let upload_limit =
+            std::env::var("LOCKFILE_SIZE_LIMIT").map_or(DEFAULT_UPLOAD_LIMIT, |limit| {
+                limit
+                    .parse()
+                    .unwrap_or_else(|err| panic!("Invalid LOCKFILE_SIZE_LIMIT {limit}: {err}"))
+            });
+        Self::with_config(advisory_db.as_deref(), upload_limit)
+    }
+
+    /// Loads the advisories from `advisory_db`, if given, and reads at most `upload_limit` bytes
+    /// of lockfiles per request.
+    fn with_config(advisory_db: Option<&std::path::Path>, upload_limit: u64) -> Self {
+        Self {
+            advisories: advisory_db.map(Advisories::load).unwrap_or_default(),
+            upload_limit,
+        }
+    }
+}
 
 #[poem_openapi::OpenApi(prefix_path = "/23")]
 impl Api {
@@ -61,7 +587,7 @@ impl Api {
 
     #[oai(path = "/lockfile", method = "post")]
     async fn lockfile(&self, body: Multipart) -> poem::Result<Html<String>> {
-        let lockfile = parse_lockfile(body).await?;
+        let lockfile = parse_lockfile::<Checksum>(body, self.upload_limit).await?;
         let mut res = Vec::with_capacity(lockfile.package.len());
         for i in lockfile.package.into_iter().filter_map(|p| p.checksum) {
             if i.len() < 10 {
@@ -77,6 +603,116 @@ impl Api {
         }
         Ok(Html(res.join("\n")))
     }
+
+    /// The dependency graph of a lockfile, in DOT if `Accept` asks for `text/vnd.graphviz`.
+    #[oai(path = "/lockfile/graph", method = "post")]
+    async fn lockfile_graph(
+        &self,
+        #[oai(name = "Accept")] accept: Header<Option<String>>,
+        body: Multipart,
+    ) -> poem::Result<GraphResponse> {
+        let mut graph = Graph {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        };
+        for lockfile in parse_lockfiles(body, self.upload_limit).await? {
+            graph.merge(lockfile.graph().map_err(err_entity)?);
+        }
+        if accept
+            .0
+            .is_some_and(|accept| accept.contains("text/vnd.graphviz"))
+        {
+            Ok(GraphResponse::Dot(PlainText(graph.dot())))
+        } else {
+            Ok(GraphResponse::Json(Json(graph)))
+        }
+    }
+
+    #[oai(path = "/lockfile/duplicates", method = "post")]
+    async fn lockfile_duplicates(&self, body: Multipart) -> poem::Result<Json<Vec<Duplicate>>> {
+        let lockfile: LockfileParsed = parse_lockfile(body, self.upload_limit).await?;
+        let mut versions = BTreeMap::<String, Vec<String>>::new();
+        for package in lockfile.package {
+            versions
+                .entry(package.name)
+                .or_default()
+                .push(package.version);
+        }
+        let duplicates = versions
+            .into_iter()
+            .filter_map(|(name, mut versions)| {
+                versions.sort_by_cached_key(|v| semver::Version::parse(v).ok());
+                versions.dedup();
+                (versions.len() > 1).then_some(Duplicate { name, versions })
+            })
+            .collect();
+        Ok(Json(duplicates))
+    }
+
+    /// The locked packages affected by known advisories, as an HTML fragment for htmx or when
+    /// `Accept` asks for HTML.
+    #[oai(path = "/lockfile/advisories", method = "post")]
+    async fn lockfile_advisories(
+        &self,
+        #[oai(name = "HX-Request")] hx_request: Header<Option<String>>,
+        #[oai(name = "Accept")] accept: Header<Option<String>>,
+        body: Multipart,
+    ) -> poem::Result<AdvisoryResponse> {
+        let matches = self
+            .advisories
+            .check(&parse_lockfile(body, self.upload_limit).await?);
+        if wants_html(&hx_request, &accept) {
+            Ok(AdvisoryResponse::Html(Html(advisories_html(&matches))))
+        } else {
+            Ok(AdvisoryResponse::Json(Json(matches)))
+        }
+    }
+
+    /// What changed from the lockfile in the field `old` to that in `new`, as an HTML table for
+    /// htmx or when `Accept` asks for HTML.
+    #[oai(path = "/lockfile/diff", method = "post")]
+    async fn lockfile_diff(
+        &self,
+        #[oai(name = "HX-Request")] hx_request: Header<Option<String>>,
+        #[oai(name = "Accept")] accept: Header<Option<String>>,
+        body: Multipart,
+    ) -> poem::Result<DiffResponse> {
+        let (old, new) = parse_lockfile_pair(body, self.upload_limit).await?;
+        let diff = LockfileDiff::new(&old, &new);
+        if wants_html(&hx_request, &accept) {
+            Ok(DiffResponse::Html(Html(diff.html())))
+        } else {
+            Ok(DiffResponse::Json(Json(diff)))
+        }
+    }
+
+    /// Packages from git or registries other than crates.io. Those without a source are local.
+    #[oai(path = "/lockfile/sources", method = "post")]
+    async fn lockfile_sources(&self, body: Multipart) -> poem::Result<Json<Vec<ForeignPackage>>> {
+        let lockfile: LockfileParsed = parse_lockfile(body, self.upload_limit).await?;
+        let foreign = lockfile
+            .package
+            .into_iter()
+            .filter_map(|p| {
+                let source = p.source.filter(|s| !CRATES_IO.contains(&s.as_str()))?;
+                Some(ForeignPackage {
+                    name: p.name,
+                    version: p.version,
+                    source,
+                })
+            })
+            .collect();
+        Ok(Json(foreign))
+    }
+}
+
+/// Whether to answer with HTML, for htmx or a browser, rather than JSON.
+fn wants_html(hx_request: &Header<Option<String>>, accept: &Header<Option<String>>) -> bool {
+    hx_request.0.as_deref() == Some("true")
+        || accept
+            .0
+            .as_deref()
+            .is_some_and(|accept| accept.contains("text/html"))
 }
 
 fn err_entity<T: std::fmt::Display>(err: T) -> poem::Error {
@@ -89,12 +725,213 @@ fn err_processing<T: std::fmt::Display>(err: T) -> poem::Error {
     poem::Error::from_status(http::StatusCode::BAD_REQUEST)
 }
 
-async fn parse_lockfile(mut body: Multipart) -> poem::Result<LockfileParsed> {
-    let Some(field) = body.next_field().await.map_err(err_processing)? else {
+/// Reads a field, as long as it is within the `remaining` bytes of the upload limit.
+async fn read_field(field: Field, remaining: &mut u64) -> poem::Result<String> {
+    let mut data = Vec::new();
+    field
+        .into_async_read()
+        .take(remaining.saturating_add(1))
+        .read_to_end(&mut data)
+        .await
+        .map_err(err_processing)?;
+    *remaining = remaining
+        .checked_sub(data.len() as u64)
+        .ok_or_else(|| poem::Error::from_status(http::StatusCode::PAYLOAD_TOO_LARGE))?;
+    String::from_utf8(data).map_err(err_processing)
+}
+
+/// Reads every lockfile uploaded in a field named `lockfile`, up to `limit` bytes in all.
+async fn parse_lockfiles<P: DeserializeOwned>(
+    mut body: Multipart,
+    limit: u64,
+) -> poem::Result<Vec<LockfileParsed<P>>> {
+    let mut remaining = limit;
+    let mut lockfiles = Vec::new();
+    while let Some(field) = body.next_field().await.map_err(err_processing)? {
+        if field.name() != Some("lockfile") {
+            continue;
+        }
+        let field = read_field(field, &mut remaining).await?;
+        lockfiles.push(toml::from_str::<LockfileParsed<P>>(&field).map_err(err_processing)?);
+    }
+    if lockfiles.is_empty() {
         return Err(poem::Error::from_status(http::StatusCode::BAD_REQUEST));
-    };
+    }
+    Ok(lockfiles)
+}
+
+/// The packages of all the uploaded lockfiles together.
+async fn parse_lockfile<P: DeserializeOwned>(
+    body: Multipart,
+    limit: u64,
+) -> poem::Result<LockfileParsed<P>> {
+    let lockfiles = parse_lockfiles(body, limit).await?;
+    Ok(LockfileParsed {
+        package: lockfiles.into_iter().flat_map(|l| l.package).collect(),
+    })
+}
+
+/// Reads the lockfiles in the fields `old` and `new`.
+async fn parse_lockfile_pair(
+    mut body: Multipart,
+    limit: u64,
+) -> poem::Result<(LockfileParsed, LockfileParsed)> {
+    let mut remaining = limit;
+    let (mut old, mut new) = (None, None);
+    while let Some(field) = body.next_field().await.map_err(err_processing)? {
+        let lockfile = match field.name() {
+            Some("old") => &mut old,
+            Some("new") => &mut new,
+            _ => continue,
+        };
+        let field = read_field(field, &mut remaining).await?;
+        *lockfile = Some(toml::from_str::<LockfileParsed>(&field).map_err(err_processing)?);
+    }
+    match (old, new) {
+        (Some(old), Some(new)) => Ok((old, new)),
+        _ => Err(err_processing(
+            "Both an `old` and a `new` lockfile are needed",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use poem::test::{TestClient, TestForm};
+    use poem::{Endpoint, Route};
+    use poem_openapi::OpenApiService;
+    use std::path::{Path, PathBuf};
+    use std::sync::OnceLock;
+
+    const LOCKFILE: &str = r#"
+version = 3
+
+[[package]]
+name = "bitflags"
+version = "1.3.2"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+
+[[package]]
+name = "bitflags"
+version = "2.6.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+"#;
+
+    const ADVISORY: &str = r#"```toml
+[advisory]
+id = "RUSTSEC-2024-0001"
+package = "bitflags"
+date = "2024-01-02"
+url = "https://example.com/bitflags"
+
+[versions]
+patched = [">= 2.0.0"]
+unaffected = ["< 1.0.0"]
+```
+
+# Flags <can> be flipped
+
+Details.
+"#;
 
-    let field = field.text().await.map_err(err_processing)?;
+    const WITHDRAWN: &str = r#"```toml
+[advisory]
+id = "RUSTSEC-2024-0002"
+package = "bitflags"
+date = "2024-02-03"
+withdrawn = "2024-02-04"
 
-    toml::from_str::<LockfileParsed>(&field).map_err(err_processing)
+[versions]
+patched = []
+```
+
+# Never mind
+"#;
+
+    /// A database of the advisories above.
+    fn advisory_db() -> &'static Path {
+        static DB: OnceLock<PathBuf> = OnceLock::new();
+        DB.get_or_init(|| {
+            let dir = std::env::temp_dir().join(format!("advisory-db-{}", std::process::id()));
+            let crates = dir.join("crates").join("bitflags");
+            std::fs::create_dir_all(&crates).unwrap();
+            std::fs::write(crates.join("RUSTSEC-2024-0001.md"), ADVISORY).unwrap();
+            std::fs::write(crates.join("RUSTSEC-2024-0002.md"), WITHDRAWN).unwrap();
+            std::fs::write(dir.join("README.md"), "# Advisories\n").unwrap();
+            dir
+        })
+    }
+
+    /// Only the day 23 endpoints, configured without the environment.
+    fn day_23_client(advisory_db: Option<&Path>, upload_limit: u64) -> TestClient<impl Endpoint> {
+        let api = Api::with_config(advisory_db, upload_limit);
+        TestClient::new(Route::new().nest("/", OpenApiService::new(api, "Day 23", "1.0")))
+    }
+
+    #[tokio::test]
+    async fn test_lockfile_advisories() {
+        let client = day_23_client(Some(advisory_db()), DEFAULT_UPLOAD_LIMIT);
+        let res = client
+            .post("/23/lockfile/advisories")
+            .header("Accept", "application/json")
+            .multipart(TestForm::new().text("lockfile", LOCKFILE))
+            .send()
+            .await;
+        res.assert_status_is_ok();
+        res.assert_json(serde_json::json!([{
+            "package": "bitflags",
+            "version": "1.3.2",
+            "id": "RUSTSEC-2024-0001",
+            "title": "Flags <can> be flipped",
+            "date": "2024-01-02",
+            "url": "https://example.com/bitflags",
+            "patched": [">=2.0.0"]
+        }]))
+        .await;
+
+        let res = client
+            .post("/23/lockfile/advisories")
+            .header("HX-Request", "true")
+            .multipart(TestForm::new().text("lockfile", LOCKFILE))
+            .send()
+            .await;
+        res.assert_status_is_ok();
+        res.assert_text(concat!(
+            r#"<ul id="advisories"><li class="advisory">"#,
+            r#"<a href="https://example.com/bitflags">RUSTSEC-2024-0001</a> "#,
+            r#"<span class="package">bitflags 1.3.2</span> Flags &lt;can&gt; be flipped</li></ul>"#,
+        ))
+        .await;
+
+        let clean = "[[package]]\nname = \"bitflags\"\nversion = \"2.6.0\"\n";
+        client
+            .post("/23/lockfile/advisories")
+            .header("Accept", "text/html")
+            .multipart(TestForm::new().text("lockfile", clean))
+            .send()
+            .await
+            .assert_text(r#"<div id="advisories" class="clean">No known advisories</div>"#)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_lockfile_too_large() {
+        let client = day_23_client(None, 4096);
+        let sources = |form: TestForm| client.post("/23/lockfile/sources").multipart(form).send();
+        let package = "[[package]]\nname = \"a\"\nversion = \"1.0.0\"\n";
+        sources(TestForm::new().text("lockfile", package.repeat(50)))
+            .await
+            .assert_status_is_ok();
+        sources(TestForm::new().text("lockfile", package.repeat(200)))
+            .await
+            .assert_status(http::StatusCode::PAYLOAD_TOO_LARGE);
+        sources(
+            TestForm::new()
+                .text("lockfile", package.repeat(60))
+                .text("lockfile", package.repeat(60)),
+        )
+        .await
+        .assert_status(http::StatusCode::PAYLOAD_TOO_LARGE);
+    }
 }
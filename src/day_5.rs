@@ -1,18 +1,23 @@
 use cargo_manifest::Manifest;
 use poem::web::headers::ContentType;
-use poem::web::TypedHeader;
-use poem::Body;
-use poem_openapi::payload::PlainText;
-use poem_openapi::OpenApi;
+use poem::web::{Multipart, TypedHeader};
+use poem::{Body, FromRequest, Request, RequestBody};
+use poem_openapi::param::{Header, Query};
+use poem_openapi::payload::{Json, PlainText};
+use poem_openapi::{Enum, Object, OpenApi};
 use serde::Deserialize;
+use serde_json::{Map, Value};
+use std::fmt::Write;
 
+/// Reads each element of a sequence as a plain value before deserializing it, so that one bad
+/// element is kept as its error instead of failing the whole sequence.
 struct SkipErrorVisitor<T>(std::marker::PhantomData<T>);
 
 impl<'de, T> serde::de::Visitor<'de> for SkipErrorVisitor<T>
 where
-    T: Deserialize<'de>,
+    T: serde::de::DeserializeOwned,
 {
-    type Value = Vec<T>;
+    type Value = Vec<Result<T, String>>;
 
     fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(formatter, "a sequence")
@@ -24,22 +29,18 @@ where
     {
         let mut values = Vec::new();
 
-        loop {
-            match seq.next_element::<T>() {
-                Ok(Some(value)) => values.push(value),
-                Ok(None) => break,
-                Err(_) => continue,
-            }
+        while let Some(value) = seq.next_element::<Value>()? {
+            values.push(T::deserialize(value).map_err(|err| err.to_string()));
         }
 
         Ok(values)
     }
 }
 
-fn skip_deserialize_errors<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+fn skip_deserialize_errors<'de, D, T>(deserializer: D) -> Result<Vec<Result<T, String>>, D::Error>
 where
     D: serde::Deserializer<'de>,
-    T: Deserialize<'de>,
+    T: serde::de::DeserializeOwned,
 {
     deserializer.deserialize_seq(SkipErrorVisitor(std::marker::PhantomData))
 }
@@ -48,7 +49,7 @@ where
 struct Metadata {
     #[serde(deserialize_with = "skip_deserialize_errors")]
     #[serde(default)]
-    orders: Vec<Order>,
+    orders: Vec<Result<Order, String>>,
 }
 
 #[derive(Deserialize)]
@@ -56,12 +57,27 @@ struct Order {
     item: String,
     #[serde(default)]
     quantity: Option<u32>,
+    // per unit, in `currency`, and like `currency` only read for the JSON report, so that the
+    // plain text listing keeps orders whatever their price
+    #[serde(default)]
+    price: Option<Value>,
+    #[serde(default)]
+    currency: Option<Value>,
 }
 
 #[derive(Debug, poem_openapi::ApiResponse)]
 enum MyResponse {
     #[oai(status = 200)]
     Ok(PlainText<String>),
+    #[oai(status = 200)]
+    Report(Json<ValidationReport>),
+    #[oai(status = 200)]
+    Orders(Json<OrderReport>),
+    /// The report when some rule failed.
+    #[oai(status = 400)]
+    Rejected(Json<OrderReport>),
+    #[oai(status = 200)]
+    Workspace(Json<WorkspaceReport>),
     #[oai(status = 204)]
     NoContent,
     #[oai(status = 400)]
@@ -70,36 +86,1281 @@ enum MyResponse {
     UnsupportedMediaType,
 }
 
+#[derive(Debug, Object)]
+struct AcceptedOrder {
+    index: usize,
+    item: String,
+    quantity: u32,
+}
+
+/// An order left out, by its position in `package.metadata.orders`.
+#[derive(Debug, Object)]
+struct RejectedOrder {
+    index: usize,
+    reason: String,
+}
+
+/// All the orders for one item, which must agree on its price. Amounts are decimals, like `4.99`.
+#[derive(Debug, Object)]
+struct ItemTotal {
+    item: String,
+    quantity: u32,
+    unit_price: Option<String>,
+    currency: Option<String>,
+    subtotal: Option<String>,
+    #[oai(skip)]
+    price: Option<Cents>,
+}
+
+/// The sum of the subtotals in one currency, as different currencies are not added up.
+#[derive(Debug, Object)]
+struct Total {
+    currency: Option<String>,
+    amount: String,
+    #[oai(skip)]
+    cents: Cents,
+}
+
+#[derive(Debug, Object)]
+struct OrderReport {
+    /// Whether every rule passed.
+    accepted: bool,
+    rules: Vec<RuleResult>,
+    orders: Vec<AcceptedOrder>,
+    rejected: Vec<RejectedOrder>,
+    items: Vec<ItemTotal>,
+    totals: Vec<Total>,
+}
+
+/// The rules of `day_5_rules.toml`, where their kinds are described.
+#[derive(Debug, Deserialize)]
+struct Rules {
+    rule: Vec<Rule>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Rule {
+    name: String,
+    message: Option<String>,
+    #[serde(flatten)]
+    check: Check,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+enum Check {
+    RequiredKeywords { keywords: Vec<String> },
+    AllowedLicenses { licenses: Vec<String> },
+    BannedDependencies { dependencies: Vec<String> },
+    MaxOrderQuantity { quantity: u32 },
+}
+
+#[derive(Debug, Object)]
+struct RuleResult {
+    name: String,
+    passed: bool,
+    /// Why the rule failed.
+    message: Option<String>,
+}
+
+impl Rules {
+    /// The rules in the file at `MANIFEST_RULES`, or else the defaults.
+    fn load() -> Self {
+        let (source, text) = match std::env::var("MANIFEST_RULES") {
+            Ok(path) => {
+                let text = std::fs::read_to_string(&path)
+                    .unwrap_or_else(|err| panic!("Failed to read {path}: {err}"));
+                (path, text)
+            }
+            Err(_) => (
+                "day_5_rules.toml".to_string(),
+                include_str!("day_5_rules.toml").to_string(),
+            ),
+        };
+        toml::from_str(&text).unwrap_or_else(|err| panic!("Invalid rules in {source}: {err}"))
+    }
+
+    fn check(&self, manifest: &Manifest<Metadata>, items: &[ItemTotal]) -> Vec<RuleResult> {
+        self.rule
+            .iter()
+            .map(|rule| {
+                let failure = rule.check.failure(manifest, items);
+                RuleResult {
+                    name: rule.name.clone(),
+                    passed: failure.is_none(),
+                    message: failure.map(|failure| rule.message.clone().unwrap_or(failure)),
+                }
+            })
+            .collect()
+    }
+}
+
+impl Check {
+    /// Why `manifest` fails the check, if it does.
+    fn failure(&self, manifest: &Manifest<Metadata>, items: &[ItemTotal]) -> Option<String> {
+        let package = manifest.package.as_ref();
+        match self {
+            Check::RequiredKeywords { keywords } => {
+                let present = package
+                    .and_then(|p| p.keywords.as_ref())
+                    .and_then(|k| k.as_ref().as_local())
+                    .map_or(&[][..], Vec::as_slice);
+                let missing: Vec<&str> = keywords
+                    .iter()
+                    .filter(|keyword| !present.contains(keyword))
+                    .map(String::as_str)
+                    .collect();
+                (!missing.is_empty()).then(|| format!("Missing keywords: {}", missing.join(", ")))
+            }
+            Check::AllowedLicenses { licenses } => {
+                let Some(license) = package
+                    .and_then(|p| p.license.as_ref())
+                    .and_then(|l| l.as_ref().as_local())
+                else {
+                    return Some("No license given".to_string());
+                };
+                // an SPDX expression, like `(MIT OR Apache-2.0) AND Unicode-3.0`
+                let denied: Vec<&str> = license
+                    .split(|c: char| c.is_whitespace() || "()/".contains(c))
+                    .filter(|id| !id.is_empty() && !["OR", "AND", "WITH"].contains(id))
+                    .filter(|id| !licenses.iter().any(|allowed| allowed == id))
+                    .collect();
+                (!denied.is_empty()).then(|| format!("Licenses not allowed: {}", denied.join(", ")))
+            }
+            Check::BannedDependencies { dependencies } => {
+                let tables = [
+                    &manifest.dependencies,
+                    &manifest.dev_dependencies,
+                    &manifest.build_dependencies,
+                ];
+                let targets = manifest.target.iter().flat_map(|t| t.values());
+                let mut banned: Vec<&str> = tables
+                    .into_iter()
+                    .flatten()
+                    .chain(targets.flat_map(|target| {
+                        [
+                            &target.dependencies,
+                            &target.dev_dependencies,
+                            &target.build_dependencies,
+                        ]
+                    }))
+                    .flat_map(|table| table.iter())
+                    .map(|(name, dependency)| dependency.package().unwrap_or(name))
+                    .filter(|name| dependencies.iter().any(|banned| banned == name))
+                    .collect();
+                banned.sort_unstable();
+                banned.dedup();
+                (!banned.is_empty()).then(|| format!("Banned dependencies: {}", banned.join(", ")))
+            }
+            Check::MaxOrderQuantity { quantity } => {
+                let over: Vec<String> = items
+                    .iter()
+                    .filter(|item| item.quantity > *quantity)
+                    .map(|item| format!("{} ({})", item.item, item.quantity))
+                    .collect();
+                (!over.is_empty())
+                    .then(|| format!("More than {quantity} ordered of: {}", over.join(", ")))
+            }
+        }
+    }
+}
+
+/// An amount of money in hundredths of its currency, so that amounts add up exactly.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Cents(u128);
+
+impl Cents {
+    /// Reads a price like `4.99`, `150` or `"4.99"`, which may not go below a cent.
+    fn parse(price: &Value) -> Result<Self, String> {
+        let invalid = || "`price` must be a number of at least 0 with at most 2 decimals";
+        let text = match price {
+            Value::Number(number) => number.to_string(),
+            Value::String(text) => text.clone(),
+            _ => return Err(invalid().to_string()),
+        };
+        let (units, decimals) = text.split_once('.').unwrap_or((&text, ""));
+        if units.is_empty()
+            || decimals.len() > 2
+            || !units
+                .chars()
+                .chain(decimals.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid().to_string());
+        }
+        let units: u64 = units.parse().map_err(|_| invalid().to_string())?;
+        let decimals: u128 = format!("{decimals:0<2}").parse().unwrap_or_default();
+        Ok(Self(u128::from(units) * 100 + decimals))
+    }
+}
+
+impl std::fmt::Display for Cents {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{:02}", self.0 / 100, self.0 % 100)
+    }
+}
+
+impl OrderReport {
+    fn new(orders: Vec<Result<Order, String>>) -> Self {
+        let mut report = OrderReport {
+            accepted: true,
+            rules: Vec::new(),
+            orders: Vec::new(),
+            rejected: Vec::new(),
+            items: Vec::new(),
+            totals: Vec::new(),
+        };
+        for (index, order) in orders.into_iter().enumerate() {
+            if let Err(reason) = order.and_then(|order| report.take(index, order)) {
+                report.rejected.push(RejectedOrder { index, reason });
+            }
+        }
+        for item in &mut report.items {
+            let Some(price) = item.price else {
+                continue;
+            };
+            let subtotal = Cents(price.0 * u128::from(item.quantity));
+            item.subtotal = Some(subtotal.to_string());
+            match report
+                .totals
+                .iter_mut()
+                .find(|t| t.currency == item.currency)
+            {
+                Some(total) => total.cents.0 += subtotal.0,
+                None => report.totals.push(Total {
+                    currency: item.currency.clone(),
+                    amount: String::new(),
+                    cents: subtotal,
+                }),
+            }
+        }
+        for total in &mut report.totals {
+            total.amount = total.cents.to_string();
+        }
+        report
+    }
+
+    fn check(&mut self, manifest: &Manifest<Metadata>, rules: &Rules) {
+        self.rules = rules.check(manifest, &self.items);
+        self.accepted = self.rules.iter().all(|rule| rule.passed);
+    }
+
+    /// Adds an order to the total of its item.
+    fn take(&mut self, index: usize, order: Order) -> Result<(), String> {
+        let quantity = order.quantity.ok_or("missing field `quantity`")?;
+        let price = order.price.as_ref().map(Cents::parse).transpose()?;
+        let currency = match order.currency {
+            None => None,
+            Some(Value::String(currency)) => Some(currency),
+            Some(_) => return Err("`currency` must be a string".to_string()),
+        };
+        match self.items.iter_mut().find(|i| i.item == order.item) {
+            Some(item) => {
+                if (item.price, &item.currency) != (price, &currency) {
+                    return Err(format!(
+                        "the price differs from an earlier order of `{}`",
+                        order.item
+                    ));
+                }
+                item.quantity = item
+                    .quantity
+                    .checked_add(quantity)
+                    .ok_or_else(|| format!("too many `{}` ordered", order.item))?;
+            }
+            None => self.items.push(ItemTotal {
+                item: order.item.clone(),
+                quantity,
+                unit_price: price.map(|price| price.to_string()),
+                currency,
+                subtotal: None,
+                price,
+            }),
+        }
+        self.orders.push(AcceptedOrder {
+            index,
+            item: order.item,
+            quantity,
+        });
+        Ok(())
+    }
+}
+
+#[derive(Debug, poem_openapi::ApiResponse)]
+enum ConvertResponse {
+    #[oai(status = 200, content_type = "application/toml")]
+    Toml(PlainText<String>),
+    #[oai(status = 200, content_type = "application/json")]
+    Json(PlainText<String>),
+    #[oai(status = 200, content_type = "application/yaml")]
+    Yaml(PlainText<String>),
+    #[oai(status = 400)]
+    BadRequest(PlainText<String>),
+    #[oai(status = 406)]
+    NotAcceptable,
+    #[oai(status = 415)]
+    UnsupportedMediaType,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl Format {
+    /// The format of a media type like `application/toml`.
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        if media_type.contains("toml") {
+            Some(Self::Toml)
+        } else if media_type.contains("json") {
+            Some(Self::Json)
+        } else if media_type.contains("yaml") {
+            Some(Self::Yaml)
+        } else {
+            None
+        }
+    }
+
+    /// The first format listed in an `Accept` header, ignoring weights. `*/*` picks `fallback`.
+    fn accepted(accept: &str, fallback: Self) -> Option<Self> {
+        accept.split(',').find_map(|range| {
+            let media_type = range.split(';').next().unwrap_or_default().trim();
+            match media_type {
+                "*/*" => Some(fallback),
+                _ => Self::from_media_type(media_type),
+            }
+        })
+    }
+
+    /// Parses into a YAML value, whose mappings keep the order of keys, unlike JSON objects.
+    fn parse(self, text: &str) -> Result<serde_yml::Value, String> {
+        match self {
+            Self::Toml => toml::from_str(text).map_err(|err| err.message().to_string()),
+            Self::Json => serde_json::from_str(text).map_err(|err| err.to_string()),
+            Self::Yaml => serde_yml::from_str(text).map_err(|err| err.to_string()),
+        }
+    }
+}
+
+/// Rewrites a manifest in another format, or normalises it in its own, keeping the order of
+/// keys. Comments are lost, as the manifest is always written again from its values.
+fn convert(text: &str, from: Format, to: Format) -> Result<String, String> {
+    let value = from.parse(text)?;
+    match to {
+        Format::Toml => {
+            toml::to_string(&value).map_err(|err| format!("Cannot be written as TOML: {err}"))
+        }
+        Format::Json => serde_json::to_string_pretty(&value)
+            .map(|json| json + "\n")
+            .map_err(|err| err.to_string()),
+        Format::Yaml => serde_yml::to_string(&value).map_err(|err| err.to_string()),
+    }
+}
+
 impl std::fmt::Display for Order {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}: {}", self.item, self.quantity.unwrap_or_default())
     }
 }
 
-fn parse_manifest(manifest: Manifest<Metadata>) -> MyResponse {
-    let Some(package) = manifest.package else {
-        return MyResponse::BadRequest(PlainText("".to_string()));
+#[derive(Debug, Clone, Copy, PartialEq, Enum)]
+#[oai(rename_all = "snake_case")]
+enum ProblemKind {
+    Syntax,
+    UnknownKey,
+    MissingField,
+    InvalidType,
+    InvalidValue,
+}
+
+/// A problem found in a manifest.
+#[derive(Debug, Object)]
+struct Problem {
+    kind: ProblemKind,
+    /// Where in the manifest, like `dependencies.serde.version`. Empty for the whole manifest.
+    path: String,
+    message: String,
+    /// From 1, when the problem could be found in a TOML or YAML source.
+    line: Option<usize>,
+    column: Option<usize>,
+}
+
+#[derive(Debug, Object)]
+struct ValidationReport {
+    valid: bool,
+    problems: Vec<Problem>,
+}
+
+#[derive(Clone, Debug)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+fn path_string(path: &[Segment]) -> String {
+    let mut result = String::new();
+    for segment in path {
+        match segment {
+            Segment::Key(key) if result.is_empty() => result.push_str(key),
+            Segment::Key(key) => write!(result, ".{key}").unwrap(),
+            Segment::Index(index) => write!(result, "[{index}]").unwrap(),
+        }
+    }
+    result
+}
+
+const MANIFEST_KEYS: &[&str] = &[
+    "cargo-features",
+    "package",
+    "project",
+    "lib",
+    "bin",
+    "example",
+    "test",
+    "bench",
+    "dependencies",
+    "dev-dependencies",
+    "dev_dependencies",
+    "build-dependencies",
+    "build_dependencies",
+    "target",
+    "features",
+    "patch",
+    "replace",
+    "profile",
+    "workspace",
+    "badges",
+    "lints",
+];
+const DEPENDENCY_TABLES: &[&str] = &[
+    "dependencies",
+    "dev-dependencies",
+    "dev_dependencies",
+    "build-dependencies",
+    "build_dependencies",
+];
+const PACKAGE_KEYS: &[&str] = &[
+    "name",
+    "version",
+    "authors",
+    "edition",
+    "rust-version",
+    "description",
+    "documentation",
+    "readme",
+    "homepage",
+    "repository",
+    "license",
+    "license-file",
+    "keywords",
+    "categories",
+    "workspace",
+    "build",
+    "links",
+    "exclude",
+    "include",
+    "publish",
+    "metadata",
+    "default-run",
+    "autolib",
+    "autobins",
+    "autoexamples",
+    "autotests",
+    "autobenches",
+    "resolver",
+];
+const DEPENDENCY_KEYS: &[&str] = &[
+    "version",
+    "path",
+    "git",
+    "branch",
+    "tag",
+    "rev",
+    "features",
+    "optional",
+    "default-features",
+    "default_features",
+    "package",
+    "registry",
+    "registry-index",
+    "workspace",
+    "public",
+    "artifact",
+    "lib",
+    "target",
+];
+const WORKSPACE_KEYS: &[&str] = &[
+    "members",
+    "exclude",
+    "default-members",
+    "resolver",
+    "package",
+    "dependencies",
+    "lints",
+    "metadata",
+];
+const PROFILE_KEYS: &[&str] = &[
+    "opt-level",
+    "debug",
+    "split-debuginfo",
+    "strip",
+    "debug-assertions",
+    "overflow-checks",
+    "lto",
+    "panic",
+    "incremental",
+    "codegen-units",
+    "rpath",
+    "inherits",
+    "package",
+    "build-override",
+];
+const EDITIONS: &[&str] = &["2015", "2018", "2021", "2024"];
+const RESOLVERS: &[&str] = &["1", "2", "3"];
+
+fn describe(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(n) if n.is_f64() => "a float",
+        Value::Number(_) => "an integer",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "a table",
+    }
+}
+
+/// `{ workspace = true }`, taking the value from the workspace root.
+fn inherited(value: &Value) -> bool {
+    value.get("workspace") == Some(&Value::Bool(true))
+}
+
+/// Walks a manifest of any format, collecting every problem along with its path.
+#[derive(Default)]
+struct Validator {
+    path: Vec<Segment>,
+    found: Vec<(ProblemKind, Vec<Segment>, String)>,
+}
+
+impl Validator {
+    fn report(&mut self, kind: ProblemKind, message: impl Into<String>) {
+        self.found.push((kind, self.path.clone(), message.into()));
+    }
+
+    fn entry(&mut self, segment: Segment, value: &Value, check: impl FnOnce(&mut Self, &Value)) {
+        self.path.push(segment);
+        check(self, value);
+        self.path.pop();
+    }
+
+    /// Runs `check` on `table[key]` if it is there.
+    fn field(
+        &mut self,
+        table: &Map<String, Value>,
+        key: &str,
+        check: impl FnOnce(&mut Self, &Value),
+    ) {
+        if let Some(value) = table.get(key) {
+            self.entry(Segment::Key(key.to_string()), value, check);
+        }
+    }
+
+    fn expected(&mut self, what: &str, value: &Value) {
+        self.report(
+            ProblemKind::InvalidType,
+            format!("expected {what}, found {}", describe(value)),
+        );
+    }
+
+    fn table<'v>(&mut self, value: &'v Value) -> Option<&'v Map<String, Value>> {
+        let table = value.as_object();
+        if table.is_none() {
+            self.expected("a table", value);
+        }
+        table
+    }
+
+    fn string<'v>(&mut self, value: &'v Value) -> Option<&'v str> {
+        let string = value.as_str();
+        if string.is_none() {
+            self.expected("a string", value);
+        }
+        string
+    }
+
+    fn boolean(&mut self, value: &Value) -> Option<bool> {
+        let boolean = value.as_bool();
+        if boolean.is_none() {
+            self.expected("a boolean", value);
+        }
+        boolean
+    }
+
+    fn strings(&mut self, value: &Value) {
+        match value.as_array() {
+            Some(values) => {
+                for (i, value) in values.iter().enumerate() {
+                    self.entry(Segment::Index(i), value, |v, value| {
+                        v.string(value);
+                    });
+                }
+            }
+            None => self.expected("an array of strings", value),
+        }
+    }
+
+    fn one_of(&mut self, value: &Value, allowed: &[&str]) {
+        if let Some(string) = self.string(value) {
+            if !allowed.contains(&string) {
+                self.report(
+                    ProblemKind::InvalidValue,
+                    format!("`{string}` is not one of {}", allowed.join(", ")),
+                );
+            }
+        }
+    }
+
+    fn known_keys(&mut self, table: &Map<String, Value>, known: &[&str]) {
+        for key in table.keys().filter(|key| !known.contains(&key.as_str())) {
+            self.path.push(Segment::Key(key.clone()));
+            self.report(ProblemKind::UnknownKey, format!("unknown key `{key}`"));
+            self.path.pop();
+        }
+    }
+
+    fn manifest(&mut self, value: &Value) {
+        let Some(root) = self.table(value) else {
+            return;
+        };
+        self.known_keys(root, MANIFEST_KEYS);
+        if !root.contains_key("package") && !root.contains_key("workspace") {
+            self.report(
+                ProblemKind::MissingField,
+                "missing `package`, or `workspace` for a virtual manifest",
+            );
+        }
+        self.field(root, "package", Self::package);
+        for &key in DEPENDENCY_TABLES {
+            self.field(root, key, Self::dependencies);
+        }
+        self.field(root, "target", |v, value| {
+            let Some(targets) = v.table(value) else {
+                return;
+            };
+            for (cfg, target) in targets {
+                v.entry(Segment::Key(cfg.clone()), target, |v, target| {
+                    let Some(target) = v.table(target) else {
+                        return;
+                    };
+                    v.known_keys(target, DEPENDENCY_TABLES);
+                    for &key in DEPENDENCY_TABLES {
+                        v.field(target, key, Self::dependencies);
+                    }
+                });
+            }
+        });
+        self.field(root, "lib", |v, value| {
+            v.table(value);
+        });
+        for key in ["bin", "example", "test", "bench"] {
+            self.field(root, key, Self::targets);
+        }
+        self.field(root, "features", |v, value| {
+            let Some(features) = v.table(value) else {
+                return;
+            };
+            for (name, enables) in features {
+                v.entry(Segment::Key(name.clone()), enables, Self::strings);
+            }
+        });
+        self.field(root, "workspace", Self::workspace);
+        self.field(root, "profile", |v, value| {
+            let Some(profiles) = v.table(value) else {
+                return;
+            };
+            for (name, profile) in profiles {
+                v.entry(Segment::Key(name.clone()), profile, Self::profile);
+            }
+        });
+    }
+
+    fn package(&mut self, value: &Value) {
+        let Some(package) = self.table(value) else {
+            return;
+        };
+        self.known_keys(package, PACKAGE_KEYS);
+        match package.get("name") {
+            Some(name) => self.entry(Segment::Key("name".to_string()), name, |v, name| {
+                let Some(name) = v.string(name) else {
+                    return;
+                };
+                if name.is_empty()
+                    || !name
+                        .chars()
+                        .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+                {
+                    v.report(
+                        ProblemKind::InvalidValue,
+                        format!("invalid package name `{name}`"),
+                    );
+                }
+            }),
+            None => self.report(ProblemKind::MissingField, "missing `name`"),
+        }
+        self.package_fields(package, true);
+    }
+
+    /// The fields shared by `package` and `workspace.package`, where only the former may
+    /// inherit them from the workspace.
+    fn package_fields(&mut self, package: &Map<String, Value>, inheritable: bool) {
+        let check = |v: &mut Self, key: &str, check: fn(&mut Self, &Value)| {
+            v.field(package, key, |v, value| {
+                if !(inheritable && inherited(value)) {
+                    check(v, value);
+                }
+            });
+        };
+        check(self, "version", |v, value| {
+            if let Some(version) = v.string(value) {
+                if let Err(err) = semver::Version::parse(version) {
+                    v.report(
+                        ProblemKind::InvalidValue,
+                        format!("invalid semver `{version}`: {err}"),
+                    );
+                }
+            }
+        });
+        check(self, "edition", |v, value| v.one_of(value, EDITIONS));
+        check(self, "rust-version", |v, value| {
+            let Some(version) = v.string(value) else {
+                return;
+            };
+            let parts: Vec<&str> = version.split('.').collect();
+            if !(2..=3).contains(&parts.len())
+                || parts
+                    .iter()
+                    .any(|part| part.is_empty() || !part.bytes().all(|b| b.is_ascii_digit()))
+            {
+                v.report(
+                    ProblemKind::InvalidValue,
+                    format!("invalid Rust version `{version}`, expected like 1.80 or 1.80.1"),
+                );
+            }
+        });
+        for key in ["authors", "keywords", "categories", "include", "exclude"] {
+            check(self, key, Self::strings);
+        }
+        for key in [
+            "description",
+            "documentation",
+            "homepage",
+            "repository",
+            "license",
+            "license-file",
+            "links",
+            "default-run",
+            "workspace",
+        ] {
+            check(self, key, |v, value| {
+                v.string(value);
+            });
+        }
+        for key in ["readme", "build"] {
+            check(self, key, |v, value| {
+                if !value.is_boolean() {
+                    v.string(value);
+                }
+            });
+        }
+        check(self, "publish", |v, value| {
+            if !value.is_boolean() {
+                v.strings(value);
+            }
+        });
+        for key in [
+            "autolib",
+            "autobins",
+            "autoexamples",
+            "autotests",
+            "autobenches",
+        ] {
+            check(self, key, |v, value| {
+                v.boolean(value);
+            });
+        }
+        check(self, "resolver", |v, value| v.one_of(value, RESOLVERS));
+    }
+
+    fn dependencies(&mut self, value: &Value) {
+        let Some(dependencies) = self.table(value) else {
+            return;
+        };
+        for (name, dependency) in dependencies {
+            self.entry(Segment::Key(name.clone()), dependency, Self::dependency);
+        }
+    }
+
+    fn dependency(&mut self, value: &Value) {
+        let dependency = match value {
+            Value::String(_) => return self.version_req(value),
+            Value::Object(dependency) => dependency,
+            _ => return self.expected("a version requirement or a table", value),
+        };
+        self.known_keys(dependency, DEPENDENCY_KEYS);
+        self.field(dependency, "version", Self::version_req);
+        for key in [
+            "path",
+            "git",
+            "branch",
+            "tag",
+            "rev",
+            "package",
+            "registry",
+            "registry-index",
+        ] {
+            self.field(dependency, key, |v, value| {
+                v.string(value);
+            });
+        }
+        self.field(dependency, "features", Self::strings);
+        for key in ["optional", "default-features", "default_features", "public"] {
+            self.field(dependency, key, |v, value| {
+                v.boolean(value);
+            });
+        }
+        self.field(dependency, "workspace", |v, value| {
+            if v.boolean(value) == Some(false) {
+                v.report(ProblemKind::InvalidValue, "`workspace` can only be true");
+            }
+        });
+
+        let has = |key| dependency.contains_key(key);
+        let at = |v: &mut Self, key: &str, message: String| {
+            v.path.push(Segment::Key(key.to_string()));
+            v.report(ProblemKind::InvalidValue, message);
+            v.path.pop();
+        };
+        if has("workspace") {
+            for key in ["version", "path", "git", "registry", "package"] {
+                if has(key) {
+                    at(
+                        self,
+                        key,
+                        format!("`{key}` cannot be combined with `workspace`"),
+                    );
+                }
+            }
+        } else if !has("version") && !has("path") && !has("git") {
+            self.report(
+                ProblemKind::MissingField,
+                "a dependency needs `version`, `path`, `git` or `workspace`",
+            );
+        }
+        let references: Vec<&str> = ["branch", "tag", "rev"]
+            .into_iter()
+            .filter(|key| has(key))
+            .collect();
+        for &key in &references {
+            if !has("git") {
+                at(self, key, format!("`{key}` needs `git`"));
+            }
+        }
+        if references.len() > 1 {
+            self.report(
+                ProblemKind::InvalidValue,
+                "only one of `branch`, `tag` and `rev` can be given",
+            );
+        }
+    }
+
+    fn version_req(&mut self, value: &Value) {
+        if let Some(requirement) = self.string(value) {
+            if let Err(err) = semver::VersionReq::parse(requirement) {
+                self.report(
+                    ProblemKind::InvalidValue,
+                    format!("invalid version requirement `{requirement}`: {err}"),
+                );
+            }
+        }
+    }
+
+    /// `[[bin]]` and the like, which need a name.
+    fn targets(&mut self, value: &Value) {
+        let Some(targets) = value.as_array() else {
+            return self.expected("an array of tables", value);
+        };
+        for (i, target) in targets.iter().enumerate() {
+            self.entry(Segment::Index(i), target, |v, target| {
+                let Some(target) = v.table(target) else {
+                    return;
+                };
+                match target.get("name") {
+                    Some(name) => v.entry(Segment::Key("name".to_string()), name, |v, name| {
+                        v.string(name);
+                    }),
+                    None => v.report(ProblemKind::MissingField, "missing `name`"),
+                }
+            });
+        }
+    }
+
+    fn workspace(&mut self, value: &Value) {
+        let Some(workspace) = self.table(value) else {
+            return;
+        };
+        self.known_keys(workspace, WORKSPACE_KEYS);
+        for key in ["members", "exclude", "default-members"] {
+            self.field(workspace, key, Self::strings);
+        }
+        self.field(workspace, "resolver", |v, value| v.one_of(value, RESOLVERS));
+        self.field(workspace, "package", |v, value| {
+            if let Some(package) = v.table(value) {
+                v.package_fields(package, false);
+            }
+        });
+        self.field(workspace, "dependencies", Self::dependencies);
+    }
+
+    fn profile(&mut self, value: &Value) {
+        let Some(profile) = self.table(value) else {
+            return;
+        };
+        self.known_keys(profile, PROFILE_KEYS);
+        let invalid = |v: &mut Self, value: &Value, expected: &str| {
+            v.report(
+                ProblemKind::InvalidValue,
+                format!("expected {expected}, found {value}"),
+            );
+        };
+        self.field(profile, "opt-level", |v, value| match value {
+            Value::Number(n) if n.as_u64().is_some_and(|n| n <= 3) => {}
+            Value::String(s) if s == "s" || s == "z" => {}
+            _ => invalid(v, value, "0, 1, 2, 3, \"s\" or \"z\""),
+        });
+        self.field(profile, "debug", |v, value| match value {
+            Value::Bool(_) => {}
+            Value::Number(n) if n.as_u64().is_some_and(|n| n <= 2) => {}
+            Value::String(s)
+                if [
+                    "none",
+                    "line-directives-only",
+                    "line-tables-only",
+                    "limited",
+                    "full",
+                ]
+                .contains(&s.as_str()) => {}
+            _ => invalid(v, value, "a boolean, 0, 1, 2 or a debug level name"),
+        });
+        self.field(profile, "strip", |v, value| {
+            if !value.is_boolean() {
+                v.one_of(value, &["none", "debuginfo", "symbols"]);
+            }
+        });
+        self.field(profile, "lto", |v, value| {
+            if !value.is_boolean() {
+                v.one_of(value, &["fat", "thin", "off"]);
+            }
+        });
+        self.field(profile, "panic", |v, value| {
+            v.one_of(value, &["unwind", "abort"])
+        });
+        self.field(profile, "split-debuginfo", |v, value| {
+            v.one_of(value, &["off", "packed", "unpacked"]);
+        });
+        self.field(profile, "codegen-units", |v, value| {
+            if !matches!(value.as_u64(), Some(1..)) {
+                invalid(v, value, "a positive integer");
+            }
+        });
+        for key in [
+            "debug-assertions",
+            "overflow-checks",
+            "incremental",
+            "rpath",
+        ] {
+            self.field(profile, key, |v, value| {
+                v.boolean(value);
+            });
+        }
+        self.field(profile, "inherits", |v, value| {
+            v.string(value);
+        });
+        self.field(profile, "build-override", Self::profile);
+        self.field(profile, "package", |v, value| {
+            let Some(packages) = v.table(value) else {
+                return;
+            };
+            for (name, profile) in packages {
+                v.entry(Segment::Key(name.clone()), profile, Self::profile);
+            }
+        });
+    }
+}
+
+/// The source a manifest was read from, to find where a path is in it.
+enum Source<'a> {
+    Toml(toml_edit::ImDocument<&'a str>),
+    Yaml(&'a str),
+    Json,
+}
+
+impl Source<'_> {
+    /// The line and column of `path`, or of its closest ancestor that can be found. With `key`,
+    /// the key of the last segment rather than its value.
+    fn locate(&self, path: &[Segment], key: bool) -> Option<(usize, usize)> {
+        (1..=path.len()).rev().find_map(|len| {
+            let key = key && len == path.len();
+            match self {
+                Source::Toml(document) => {
+                    let span = toml_span(document.as_item(), &path[..len], key)?;
+                    Some(position(document.raw(), span.start))
+                }
+                Source::Yaml(text) => yaml_position(text, &path[..len], key),
+                Source::Json => None,
+            }
+        })
+    }
+}
+
+fn toml_span(
+    mut item: &toml_edit::Item,
+    path: &[Segment],
+    key: bool,
+) -> Option<std::ops::Range<usize>> {
+    for (i, segment) in path.iter().enumerate() {
+        match segment {
+            Segment::Key(name) if key && i == path.len() - 1 => {
+                return item.as_table_like()?.key(name)?.span();
+            }
+            Segment::Key(name) => item = item.get(name.as_str())?,
+            Segment::Index(index) => item = item.get(*index)?,
+        }
+    }
+    item.span()
+}
+
+/// The line and column, from 1, of byte `offset` in `text`.
+fn position(text: &str, offset: usize) -> (usize, usize) {
+    let before = text.get(..offset).unwrap_or(text);
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .count()
+        + 1;
+    (line, column)
+}
+
+struct YamlLine<'a> {
+    number: usize,
+    // column of `content`, from 0
+    indent: usize,
+    // column of the `- ` starting a sequence item, if any
+    dash: Option<usize>,
+    content: &'a str,
+}
+
+/// The key and the rest of a `key: value` line.
+fn yaml_key(content: &str) -> Option<(&str, &str)> {
+    let (key, rest) = content.split_once(':')?;
+    (rest.is_empty() || rest.starts_with(' ')).then(|| {
+        let key = key.trim();
+        let key = key
+            .strip_prefix('"')
+            .and_then(|k| k.strip_suffix('"'))
+            .unwrap_or(key);
+        let key = key
+            .strip_prefix('\'')
+            .and_then(|k| k.strip_suffix('\''))
+            .unwrap_or(key);
+        (key, rest.trim_start())
+    })
+}
+
+/// Finds `path` in YAML written in block style by following the indentation. Flow style
+/// (`{ ... }` and `[ ... ]`) is not followed, so values inside it are not found.
+fn yaml_position(text: &str, path: &[Segment], key: bool) -> Option<(usize, usize)> {
+    let lines: Vec<YamlLine> = text
+        .lines()
+        .enumerate()
+        .filter_map(|(i, raw)| {
+            let content = raw.trim_start_matches(' ');
+            if content.is_empty() || content.starts_with('#') || content == "---" {
+                return None;
+            }
+            let spaces = raw.len() - content.len();
+            Some(match content.strip_prefix("- ") {
+                Some(item) => {
+                    let item = item.trim_start_matches(' ');
+                    YamlLine {
+                        number: i + 1,
+                        indent: raw.len() - item.len(),
+                        dash: Some(spaces),
+                        content: item,
+                    }
+                }
+                None => YamlLine {
+                    number: i + 1,
+                    indent: spaces,
+                    dash: None,
+                    content,
+                },
+            })
+        })
+        .collect();
+
+    let mut block = &lines[..];
+    let mut found = None;
+    for (i, segment) in path.iter().enumerate() {
+        let first = block.first()?;
+        match segment {
+            Segment::Key(name) => {
+                let indent = first.indent;
+                let at = block.iter().position(|line| {
+                    line.indent == indent && yaml_key(line.content).is_some_and(|(k, _)| k == name)
+                })?;
+                let line = &block[at];
+                let children = &block[at + 1..];
+                let end = children
+                    .iter()
+                    .position(|child| child.indent <= indent)
+                    .unwrap_or(children.len());
+                block = &children[..end];
+                let (_, rest) = yaml_key(line.content)?;
+                found = Some(if key && i == path.len() - 1 {
+                    (line.number, line.indent + 1)
+                } else if !rest.is_empty() && !rest.starts_with('#') {
+                    let offset = line.content.len() - rest.len();
+                    (
+                        line.number,
+                        line.indent + line.content[..offset].chars().count() + 1,
+                    )
+                } else {
+                    block
+                        .first()
+                        .map_or((line.number, line.indent + 1), |child| {
+                            (child.number, child.dash.unwrap_or(child.indent) + 1)
+                        })
+                });
+            }
+            Segment::Index(index) => {
+                let dash = first.dash?;
+                let items: Vec<usize> = block
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, line)| line.dash == Some(dash))
+                    .map(|(at, _)| at)
+                    .collect();
+                let &start = items.get(*index)?;
+                let end = items.get(index + 1).copied().unwrap_or_else(|| {
+                    block[start + 1..]
+                        .iter()
+                        .position(|line| line.indent <= dash)
+                        .map_or(block.len(), |at| start + 1 + at)
+                });
+                block = &block[start..end];
+                found = Some((block[0].number, block[0].indent + 1));
+            }
+        }
+    }
+    found
+}
+
+/// Checks a manifest and reports every problem found, instead of stopping at the first one.
+fn validate(format: Format, text: &str) -> ValidationReport {
+    let syntax = |message: String, location: Option<(usize, usize)>| ValidationReport {
+        valid: false,
+        problems: vec![Problem {
+            kind: ProblemKind::Syntax,
+            path: String::new(),
+            message,
+            line: location.map(|(line, _)| line),
+            column: location.map(|(_, column)| column),
+        }],
+    };
+    let (value, source) = match format {
+        Format::Toml => match toml_edit::ImDocument::parse(text) {
+            Ok(document) => match toml::from_str::<Value>(text) {
+                Ok(value) => (value, Source::Toml(document)),
+                Err(err) => return syntax(err.message().to_string(), None),
+            },
+            Err(err) => {
+                let location = err.span().map(|span| position(text, span.start));
+                return syntax(err.message().to_string(), location);
+            }
+        },
+        Format::Json => match serde_json::from_str(text) {
+            Ok(value) => (value, Source::Json),
+            Err(err) => return syntax(err.to_string(), Some((err.line(), err.column()))),
+        },
+        Format::Yaml => match serde_yml::from_str(text) {
+            Ok(value) => (value, Source::Yaml(text)),
+            Err(err) => {
+                let location = err.location().map(|l| (l.line(), l.column()));
+                return syntax(err.to_string(), location);
+            }
+        },
     };
 
-    if package.keywords.map_or(true, |x| {
-        x.as_local()
-            .map_or(true, |x| !x.iter().any(|k| k == "Christmas 2024"))
-    }) {
-        return MyResponse::BadRequest(PlainText("Magic keyword not provided".to_string()));
+    let mut validator = Validator::default();
+    validator.manifest(&value);
+    let mut problems: Vec<Problem> = validator
+        .found
+        .into_iter()
+        .map(|(kind, path, message)| {
+            let location = source.locate(&path, kind == ProblemKind::UnknownKey);
+            Problem {
+                kind,
+                path: path_string(&path),
+                message,
+                line: location.map(|(line, _)| line),
+                column: location.map(|(_, column)| column),
+            }
+        })
+        .collect();
+    // the validator walks tables in key order, so put the problems in the order of the source
+    problems.sort_by_key(|p| (p.line.is_none(), p.line, p.column));
+    ValidationReport {
+        valid: problems.is_empty(),
+        problems,
     }
+}
 
-    let body = package
+/// Lists the orders as plain text, or with `report` as JSON along with the orders left out and
+/// the totals per item.
+fn parse_manifest(mut manifest: Manifest<Metadata>, report: bool, rules: &Rules) -> MyResponse {
+    let Some(package) = manifest.package.as_mut() else {
+        return MyResponse::BadRequest(PlainText("".to_string()));
+    };
+    let orders = package
         .metadata
-        .map(|metadata| {
-            metadata
-                .orders
-                .iter()
-                .filter(|o| o.quantity.is_some())
-                .map(std::string::ToString::to_string)
-                .collect::<Vec<_>>()
-                .join("\n")
-        })
+        .take()
+        .map(|m| m.orders)
         .unwrap_or_default();
+    let lines: Vec<String> = orders
+        .iter()
+        .flatten()
+        .filter(|o| o.quantity.is_some())
+        .map(std::string::ToString::to_string)
+        .collect();
+
+    let mut checked = OrderReport::new(orders);
+    checked.check(&manifest, rules);
+    if report {
+        return if checked.accepted {
+            MyResponse::Orders(Json(checked))
+        } else {
+            MyResponse::Rejected(Json(checked))
+        };
+    }
+    if let Some(failed) = checked.rules.into_iter().find(|rule| !rule.passed) {
+        return MyResponse::BadRequest(PlainText(failed.message.unwrap_or_default()));
+    }
+
+    let body = lines.join("\n");
 
     if body.is_empty() {
         MyResponse::NoContent
@@ -108,18 +1369,296 @@ fn parse_manifest(manifest: Manifest<Metadata>) -> MyResponse {
     }
 }
 
+/// One package of a workspace, by the directory of its manifest.
+#[derive(Debug, Object)]
+struct MemberReport {
+    path: String,
+    name: Option<String>,
+    report: Option<OrderReport>,
+    /// Why the member could not be read, in which case there is no report.
+    error: Option<String>,
+}
+
+#[derive(Debug, Object)]
+struct WorkspaceReport {
+    members: Vec<MemberReport>,
+}
+
+impl MemberReport {
+    fn failed(path: &str, error: impl Into<String>) -> Self {
+        MemberReport {
+            path: path.to_string(),
+            name: None,
+            report: None,
+            error: Some(error.into()),
+        }
+    }
+
+    fn new(path: &str, text: &str, workspace: &Map<String, Value>, rules: &Rules) -> Self {
+        match toml::from_str(text) {
+            Ok(manifest) => Self::from_value(path, manifest, workspace, rules),
+            Err(err) => Self::failed(path, err.message()),
+        }
+    }
+
+    fn from_value(
+        path: &str,
+        mut manifest: Value,
+        workspace: &Map<String, Value>,
+        rules: &Rules,
+    ) -> Self {
+        let manifest = inherit(&mut manifest, workspace).and_then(|()| {
+            serde_json::from_value::<Manifest<Metadata>>(manifest).map_err(|e| e.to_string())
+        });
+        let mut manifest = match manifest {
+            Ok(manifest) if manifest.package.is_some() => manifest,
+            Ok(_) => return Self::failed(path, "missing `package`"),
+            Err(err) => return Self::failed(path, err),
+        };
+        let package = manifest.package.as_mut().unwrap();
+        let name = package.name.clone();
+        let orders = package
+            .metadata
+            .take()
+            .map(|m| m.orders)
+            .unwrap_or_default();
+        let mut report = OrderReport::new(orders);
+        report.check(&manifest, rules);
+        MemberReport {
+            path: path.to_string(),
+            name: Some(name),
+            report: Some(report),
+            error: None,
+        }
+    }
+}
+
+/// Replaces the `{ workspace = true }` fields and dependencies of a member with those in the
+/// workspace root.
+fn inherit(member: &mut Value, workspace: &Map<String, Value>) -> Result<(), String> {
+    let empty = Map::new();
+    let section = |key| {
+        workspace
+            .get(key)
+            .and_then(Value::as_object)
+            .unwrap_or(&empty)
+    };
+    let (package, dependencies) = (section("package"), section("dependencies"));
+
+    if let Some(fields) = member.get_mut("package").and_then(Value::as_object_mut) {
+        for (key, value) in fields.iter_mut().filter(|(_, value)| inherited(value)) {
+            *value = package
+                .get(key)
+                .cloned()
+                .ok_or_else(|| format!("`package.{key}` is not in `workspace.package`"))?;
+        }
+    }
+
+    let mut tables: Vec<&mut Value> = Vec::new();
+    let Some(member) = member.as_object_mut() else {
+        return Ok(());
+    };
+    for (key, value) in member.iter_mut() {
+        if DEPENDENCY_TABLES.contains(&key.as_str()) {
+            tables.push(value);
+        } else if key == "target" {
+            let targets = value
+                .as_object_mut()
+                .into_iter()
+                .flat_map(|t| t.values_mut());
+            for target in targets.filter_map(Value::as_object_mut) {
+                tables.extend(
+                    target
+                        .iter_mut()
+                        .filter(|(key, _)| DEPENDENCY_TABLES.contains(&key.as_str()))
+                        .map(|(_, value)| value),
+                );
+            }
+        }
+    }
+    for table in tables.into_iter().filter_map(Value::as_object_mut) {
+        for (name, dependency) in table.iter_mut().filter(|(_, value)| inherited(value)) {
+            let mut resolved = match dependencies.get(name) {
+                Some(Value::String(version)) => {
+                    Map::from_iter([("version".to_string(), Value::String(version.clone()))])
+                }
+                Some(Value::Object(resolved)) => resolved.clone(),
+                _ => return Err(format!("`{name}` is not in `workspace.dependencies`")),
+            };
+            // features add to those of the workspace, anything else is the member's own
+            for (key, value) in dependency.as_object().into_iter().flatten() {
+                match (key.as_str(), resolved.get_mut(key), value) {
+                    ("workspace", _, _) => {}
+                    ("features", Some(Value::Array(features)), Value::Array(more)) => {
+                        features.extend(more.iter().cloned())
+                    }
+                    _ => {
+                        resolved.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+            *dependency = Value::Object(resolved);
+        }
+    }
+    Ok(())
+}
+
+/// Whether `path` matches a workspace member pattern, where `*` stands for any part of one
+/// directory name.
+fn matches_member(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.trim_end_matches('/');
+    pattern.split('/').count() == path.split('/').count()
+        && pattern
+            .split('/')
+            .zip(path.split('/'))
+            .all(|(pattern, name)| {
+                let mut parts = pattern.split('*');
+                let first = parts.next().unwrap_or_default();
+                let Some(mut rest) = name.strip_prefix(first) else {
+                    return false;
+                };
+                let parts: Vec<&str> = parts.collect();
+                let Some((last, middle)) = parts.split_last() else {
+                    return rest.is_empty();
+                };
+                for part in middle {
+                    match rest.find(part) {
+                        Some(at) => rest = &rest[at + part.len()..],
+                        None => return false,
+                    }
+                }
+                rest.ends_with(last)
+            })
+}
+
+/// Reads a workspace uploaded as one part per `Cargo.toml`, each named by its path from the
+/// workspace root, and reports on every member. The file name of a part only counts when its
+/// field name is not such a path, as browsers and curl send just `Cargo.toml` there.
+async fn parse_workspace(mut body: Multipart, rules: &Rules) -> Result<WorkspaceReport, String> {
+    let mut root = None;
+    let mut members = Vec::new();
+    while let Some(field) = body.next_field().await.map_err(|err| err.to_string())? {
+        let name = field
+            .name()
+            .filter(|name| name.ends_with("Cargo.toml"))
+            .or(field.file_name())
+            .unwrap_or_default();
+        let name = name.trim_start_matches("./").to_string();
+        let text = field.text().await.map_err(|err| err.to_string())?;
+        match name.strip_suffix("Cargo.toml") {
+            Some("") if root.is_some() => {
+                return Err("More than one Cargo.toml for the workspace root".to_string())
+            }
+            Some("") => root = Some(text),
+            Some(dir) if dir.ends_with('/') => {
+                members.push((dir.trim_end_matches('/').to_string(), text))
+            }
+            _ => return Err(format!("`{name}` is not a path to a Cargo.toml")),
+        }
+    }
+    let root = root.ok_or("Missing the Cargo.toml of the workspace root")?;
+    let root: Value = toml::from_str(&root).map_err(|err| err.message().to_string())?;
+    let workspace = root
+        .get("workspace")
+        .and_then(Value::as_object)
+        .ok_or("The root Cargo.toml has no `workspace`")?;
+    let patterns = |key| -> Vec<&str> {
+        let patterns = workspace.get(key).and_then(Value::as_array);
+        patterns
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect()
+    };
+    let (include, exclude) = (patterns("members"), patterns("exclude"));
+
+    let mut report = WorkspaceReport {
+        members: Vec::new(),
+    };
+    if root.get("package").is_some() {
+        let member = MemberReport::from_value(".", root.clone(), workspace, rules);
+        report.members.push(member);
+    }
+    for (path, text) in &members {
+        report.members.push(
+            if include.iter().any(|pattern| matches_member(pattern, path))
+                && !exclude.iter().any(|pattern| matches_member(pattern, path))
+            {
+                MemberReport::new(path, text, workspace, rules)
+            } else {
+                MemberReport::failed(path, "not a member of the workspace")
+            },
+        );
+    }
+    for &pattern in include.iter().filter(|pattern| !pattern.contains('*')) {
+        let pattern = pattern.trim_end_matches('/');
+        if !members.iter().any(|(path, _)| path == pattern) {
+            report
+                .members
+                .push(MemberReport::failed(pattern, "missing from the upload"));
+        }
+    }
+    Ok(report)
+}
+
 fn parse_error<T: std::fmt::Display>(err: T) -> MyResponse {
     eprintln!("Failed to parse manifest: {err}");
     MyResponse::BadRequest(PlainText("Invalid manifest".to_string()))
 }
 
-pub struct Api;
+pub struct Api {
+    rules: Rules,
+}
+
+impl Api {
+    pub fn new() -> Self {
+        Self {
+            rules: Rules::load(),
+        }
+    }
+}
 
 #[OpenApi(prefix_path = "/5")]
 impl Api {
+    /// With `validate=true`, reports every problem with the manifest as JSON instead. Accepting
+    /// JSON also lists the orders left out and why. A workspace is uploaded as multipart, see
+    /// [`parse_workspace`].
     #[oai(path = "/manifest", method = "post")]
-    async fn manifest(&self, TypedHeader(ct): TypedHeader<ContentType>, body: Body) -> MyResponse {
+    async fn manifest(
+        &self,
+        req: &Request,
+        TypedHeader(ct): TypedHeader<ContentType>,
+        #[oai(name = "Accept")] accept: Header<Option<String>>,
+        validate: Query<Option<bool>>,
+        body: Body,
+    ) -> MyResponse {
         let content_type = ct.to_string();
+        if content_type.starts_with("multipart/form-data") {
+            let workspace = match Multipart::from_request(req, &mut RequestBody::new(body)).await {
+                Ok(multipart) => parse_workspace(multipart, &self.rules).await,
+                Err(err) => Err(err.to_string()),
+            };
+            return match workspace {
+                Ok(report) => MyResponse::Workspace(Json(report)),
+                Err(err) => {
+                    eprintln!("Failed to parse workspace: {err}");
+                    MyResponse::BadRequest(PlainText(err))
+                }
+            };
+        }
+        let report = accept
+            .0
+            .is_some_and(|accept| accept.contains("application/json"));
+        let parse_manifest = |manifest| parse_manifest(manifest, report, &self.rules);
+        if validate.0 == Some(true) {
+            let Some(format) = Format::from_media_type(&content_type) else {
+                return MyResponse::UnsupportedMediaType;
+            };
+            return match body.into_string().await {
+                Ok(text) => MyResponse::Report(Json(self::validate(format, &text))),
+                Err(err) => parse_error(err),
+            };
+        }
         if content_type.contains("toml") {
             Manifest::<Metadata>::from_slice_with_metadata(
                 body.into_vec().await.unwrap_or_default().as_slice(),
@@ -138,4 +1677,148 @@ impl Api {
             MyResponse::UnsupportedMediaType
         }
     }
+
+    /// Converts a manifest to the format asked for in `Accept`, or normalises it in its own
+    /// format. Comments are not kept, whatever the formats.
+    #[oai(path = "/convert", method = "post")]
+    async fn convert(
+        &self,
+        TypedHeader(ct): TypedHeader<ContentType>,
+        #[oai(name = "Accept")] accept: Header<Option<String>>,
+        body: Body,
+    ) -> ConvertResponse {
+        let Some(from) = Format::from_media_type(&ct.to_string()) else {
+            return ConvertResponse::UnsupportedMediaType;
+        };
+        let Some(to) = Format::accepted(accept.0.as_deref().unwrap_or("*/*"), from) else {
+            return ConvertResponse::NotAcceptable;
+        };
+        let text = match body.into_string().await {
+            Ok(text) => text,
+            Err(err) => return ConvertResponse::BadRequest(PlainText(err.to_string())),
+        };
+        match (self::convert(&text, from, to), to) {
+            (Ok(text), Format::Toml) => ConvertResponse::Toml(PlainText(text)),
+            (Ok(text), Format::Json) => ConvertResponse::Json(PlainText(text)),
+            (Ok(text), Format::Yaml) => ConvertResponse::Yaml(PlainText(text)),
+            (Err(message), _) => ConvertResponse::BadRequest(PlainText(message)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rules() {
+        let rules: Rules = toml::from_str(
+            r#"
+[[rule]]
+name = "keywords"
+kind = "required-keywords"
+keywords = ["Christmas 2024", "gifts"]
+
+[[rule]]
+name = "licenses"
+kind = "allowed-licenses"
+licenses = ["MIT", "Apache-2.0"]
+
+[[rule]]
+name = "no-coal"
+kind = "banned-dependencies"
+dependencies = ["coal", "openssl"]
+message = "No coal this year"
+
+[[rule]]
+name = "quantity"
+kind = "max-order-quantity"
+quantity = 10
+"#,
+        )
+        .unwrap();
+        let check = |manifest: &str| {
+            let mut manifest =
+                Manifest::<Metadata>::from_slice_with_metadata(manifest.as_bytes()).unwrap();
+            let package = manifest.package.as_mut().unwrap();
+            let orders = package
+                .metadata
+                .take()
+                .map(|m| m.orders)
+                .unwrap_or_default();
+            let mut report = OrderReport::new(orders);
+            report.check(&manifest, &rules);
+            let results: Vec<(String, Option<String>)> = report
+                .rules
+                .into_iter()
+                .map(|rule| {
+                    assert_eq!(rule.passed, rule.message.is_none());
+                    (rule.name, rule.message)
+                })
+                .collect();
+            (report.accepted, results)
+        };
+
+        let (accepted, results) = check(
+            r#"
+[package]
+name = "sleigh"
+keywords = ["gifts", "Christmas 2024"]
+license = "MIT OR Apache-2.0"
+
+[dependencies]
+serde = "1"
+
+[[package.metadata.orders]]
+item = "Toy car"
+quantity = 10
+"#,
+        );
+        assert!(accepted);
+        assert!(results.iter().all(|(_, message)| message.is_none()));
+
+        let (accepted, results) = check(
+            r#"
+[package]
+name = "sleigh"
+keywords = ["gifts"]
+license = "(MIT OR GPL-3.0) AND Unicode-3.0"
+
+[dependencies]
+fuel = { version = "1", package = "coal" }
+
+[target.'cfg(unix)'.build-dependencies]
+openssl = "0.10"
+
+[[package.metadata.orders]]
+item = "Toy car"
+quantity = 6
+
+[[package.metadata.orders]]
+item = "Toy car"
+quantity = 6
+"#,
+        );
+        assert!(!accepted);
+        assert_eq!(
+            results,
+            [
+                ("keywords", "Missing keywords: Christmas 2024"),
+                ("licenses", "Licenses not allowed: GPL-3.0, Unicode-3.0"),
+                ("no-coal", "No coal this year"),
+                ("quantity", "More than 10 ordered of: Toy car (12)"),
+            ]
+            .map(|(name, message)| (name.to_string(), Some(message.to_string())))
+        );
+
+        let (_, results) = check("[package]\nname = \"sleigh\"\n");
+        assert_eq!(results[1].1.as_deref(), Some("No license given"));
+    }
+
+    #[test]
+    fn test_default_rules() {
+        let rules = Rules::load();
+        assert_eq!(rules.rule.len(), 1);
+        assert_eq!(rules.rule[0].name, "magic-keyword");
+    }
 }
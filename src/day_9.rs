@@ -1,17 +1,256 @@
-use leaky_bucket::RateLimiter;
 use poem::http::header::CONTENT_TYPE;
 use poem::http::StatusCode;
 use poem::middleware::AddData;
-use poem::web::{Data, Json};
+use poem::web::{Data, Json, Query};
 use poem::{
-    handler, post, Endpoint, EndpointExt, Error, FromRequest, IntoResponse, Request, RequestBody,
+    handler, post, Endpoint, EndpointExt, FromRequest, IntoResponse, Request, RequestBody,
     Response, Route,
 };
 use serde::{Deserialize, Serialize};
-use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-const MAX_LITERS: usize = 5;
+const MAX_LITERS: u32 = 5;
+
+/// Token bucket refilled by `per_interval` tokens every `interval`, up to `capacity`.
+///
+/// The available tokens and the refill tick they were last brought up to date at are packed
+/// in a single atomic word, so every operation is a compare-and-swap loop and no lock is held
+/// on the request path.
+pub(crate) struct TokenBucket {
+    start: Instant,
+    interval: Duration,
+    per_interval: u32,
+    capacity: AtomicU32,
+    // tokens in the high half, last refill tick in the low half
+    state: AtomicU64,
+}
+
+fn pack(tokens: u32, tick: u32) -> u64 {
+    (u64::from(tokens) << 32) | u64::from(tick)
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn unpack(state: u64) -> (u32, u32) {
+    ((state >> 32) as u32, state as u32)
+}
+
+impl TokenBucket {
+    /// Creates a full bucket.
+    pub(crate) fn new(capacity: u32, per_interval: u32, interval: Duration) -> Self {
+        Self {
+            start: Instant::now(),
+            interval,
+            per_interval,
+            capacity: AtomicU32::new(capacity),
+            state: AtomicU64::new(pack(capacity, 0)),
+        }
+    }
+
+    fn tick(&self) -> u32 {
+        let ticks = self.start.elapsed().as_nanos() / self.interval.as_nanos().max(1);
+        u32::try_from(ticks).unwrap_or(u32::MAX)
+    }
+
+    /// Brings the bucket up to date and stores the token count returned by `f`, which receives
+    /// the current tokens and capacity. Returns false, leaving the bucket untouched, when `f`
+    /// returns `None`.
+    fn update(&self, f: impl Fn(u32, u32) -> Option<u32>) -> bool {
+        let now = self.tick();
+        self.state
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |state| {
+                let capacity = self.capacity.load(Ordering::Acquire);
+                let (tokens, last) = unpack(state);
+                let refilled = now.saturating_sub(last).saturating_mul(self.per_interval);
+                let tokens = tokens.saturating_add(refilled).min(capacity);
+                f(tokens, capacity).map(|tokens| pack(tokens, now.max(last)))
+            })
+            .is_ok()
+    }
+
+    pub(crate) fn try_acquire(&self, tokens: u32) -> bool {
+        self.update(|available, _| available.checked_sub(tokens))
+    }
+
+    /// Fills the bucket up to its capacity.
+    pub(crate) fn refill(&self) {
+        self.update(|_, capacity| Some(capacity));
+    }
+
+    /// Adds `tokens` without going over the capacity.
+    pub(crate) fn add(&self, tokens: u32) {
+        self.update(|available, capacity| Some(available.saturating_add(tokens).min(capacity)));
+    }
+
+    /// Changes the capacity, dropping the tokens that no longer fit.
+    pub(crate) fn set_capacity(&self, capacity: u32) {
+        self.capacity.store(capacity, Ordering::Release);
+        self.update(|available, capacity| Some(available.min(capacity)));
+    }
+}
+
+/// Token bucket stored in the `milk_buckets` table, so every replica sharing the database draws
+/// from the same milk supply. Each operation is a single `UPDATE` that first applies the refill
+/// intervals elapsed since `refilled_at`, so concurrent requests are serialized by the row lock.
+pub(crate) struct PgBucket {
+    pool: sqlx::PgPool,
+    name: &'static str,
+    capacity: u32,
+    per_interval: u32,
+    interval: Duration,
+    initialized: AtomicBool,
+}
+
+impl PgBucket {
+    pub(crate) fn new(
+        pool: sqlx::PgPool,
+        name: &'static str,
+        capacity: u32,
+        per_interval: u32,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            pool,
+            name,
+            capacity,
+            per_interval,
+            interval,
+            initialized: AtomicBool::new(false),
+        }
+    }
+
+    /// Creates the row with a full bucket the first time this replica touches it. Rows created by
+    /// another replica are left as they are.
+    async fn init(&self) -> Result<(), sqlx::Error> {
+        if self.initialized.load(Ordering::Acquire) {
+            return Ok(());
+        }
+        sqlx::query!(
+            "INSERT INTO milk_buckets (name, tokens, capacity, per_interval, interval_ms)
+             VALUES ($1, $2, $2, $3, $4) ON CONFLICT (name) DO NOTHING",
+            self.name,
+            i64::from(self.capacity),
+            i64::from(self.per_interval),
+            i64::try_from(self.interval.as_millis()).unwrap_or(i64::MAX),
+        )
+        .execute(&self.pool)
+        .await?;
+        self.initialized.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    pub(crate) async fn try_acquire(&self, tokens: u32) -> Result<bool, sqlx::Error> {
+        self.init().await?;
+        let res = sqlx::query!(
+            "UPDATE milk_buckets
+             SET tokens = LEAST(capacity, tokens + per_interval * milk_bucket_ticks(refilled_at, interval_ms)) - $2,
+                 refilled_at = milk_bucket_refilled_at(refilled_at, interval_ms)
+             WHERE name = $1
+               AND LEAST(capacity, tokens + per_interval * milk_bucket_ticks(refilled_at, interval_ms)) >= $2",
+            self.name,
+            i64::from(tokens),
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(res.rows_affected() == 1)
+    }
+
+    pub(crate) async fn refill(&self) -> Result<(), sqlx::Error> {
+        self.init().await?;
+        sqlx::query!(
+            "UPDATE milk_buckets SET tokens = capacity, refilled_at = now() WHERE name = $1",
+            self.name,
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub(crate) async fn add(&self, tokens: u32) -> Result<(), sqlx::Error> {
+        self.init().await?;
+        sqlx::query!(
+            "UPDATE milk_buckets
+             SET tokens = LEAST(capacity, tokens + per_interval * milk_bucket_ticks(refilled_at, interval_ms) + $2),
+                 refilled_at = milk_bucket_refilled_at(refilled_at, interval_ms)
+             WHERE name = $1",
+            self.name,
+            i64::from(tokens),
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub(crate) async fn set_capacity(&self, capacity: u32) -> Result<(), sqlx::Error> {
+        self.init().await?;
+        sqlx::query!(
+            "UPDATE milk_buckets
+             SET capacity = $2,
+                 tokens = LEAST($2, tokens + per_interval * milk_bucket_ticks(refilled_at, interval_ms)),
+                 refilled_at = milk_bucket_refilled_at(refilled_at, interval_ms)
+             WHERE name = $1",
+            self.name,
+            i64::from(capacity),
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+/// The milk rate limiter, either local to this process or shared through the database.
+///
+/// The backend is picked by `route` from the `MILK_RATE_LIMITER` environment variable
+/// (`memory`, the default, or `postgres`).
+pub(crate) enum RateLimiter {
+    Memory(TokenBucket),
+    Postgres(PgBucket),
+}
+
+impl RateLimiter {
+    pub(crate) async fn try_acquire(&self, tokens: u32) -> Result<bool, sqlx::Error> {
+        match self {
+            Self::Memory(bucket) => Ok(bucket.try_acquire(tokens)),
+            Self::Postgres(bucket) => bucket.try_acquire(tokens).await,
+        }
+    }
+
+    pub(crate) async fn refill(&self) -> Result<(), sqlx::Error> {
+        match self {
+            Self::Memory(bucket) => {
+                bucket.refill();
+                Ok(())
+            }
+            Self::Postgres(bucket) => bucket.refill().await,
+        }
+    }
+
+    pub(crate) async fn add(&self, tokens: u32) -> Result<(), sqlx::Error> {
+        match self {
+            Self::Memory(bucket) => {
+                bucket.add(tokens);
+                Ok(())
+            }
+            Self::Postgres(bucket) => bucket.add(tokens).await,
+        }
+    }
+
+    pub(crate) async fn set_capacity(&self, capacity: u32) -> Result<(), sqlx::Error> {
+        match self {
+            Self::Memory(bucket) => {
+                bucket.set_capacity(capacity);
+                Ok(())
+            }
+            Self::Postgres(bucket) => bucket.set_capacity(capacity).await,
+        }
+    }
+}
+
+fn db_error(err: &sqlx::Error) -> StatusCode {
+    eprintln!("milk rate limiter err {err}");
+    StatusCode::INTERNAL_SERVER_ERROR
+}
 
 #[derive(Deserialize, Default, Serialize)]
 struct Conversion {
@@ -32,91 +271,479 @@ fn no_body() -> Option<()> {
     Some(())
 }
 
-impl IntoResponse for Conversion {
-    fn into_response(self) -> Response {
-        match (
-            self.has_body,
-            self.liters,
-            self.gallons,
-            self.litres,
-            self.pints,
-        ) {
-            (Some(()), Some(liters), None, None, None) => Json(Conversion {
+impl Conversion {
+    /// Converts the single field set on a deserialized body.
+    fn converted(&self) -> Option<Conversion> {
+        match (self.liters, self.gallons, self.litres, self.pints) {
+            (Some(liters), None, None, None) => Some(Conversion {
                 gallons: Some(liters * 0.264_172_9),
                 ..Default::default()
-            })
-            .into_response(),
-            (Some(()), None, Some(gallons), None, None) => Json(Conversion {
+            }),
+            (None, Some(gallons), None, None) => Some(Conversion {
                 liters: Some(gallons * 3.7854),
                 ..Default::default()
-            })
-            .into_response(),
-            (Some(()), None, None, Some(litres), None) => Json(Conversion {
+            }),
+            (None, None, Some(litres), None) => Some(Conversion {
                 pints: Some(litres * 1.759_753_986_4),
                 ..Default::default()
-            })
-            .into_response(),
-            (Some(()), None, None, None, Some(pints)) => Json(Conversion {
+            }),
+            (None, None, None, Some(pints)) => Some(Conversion {
                 litres: Some(pints * 0.568_261_25),
                 ..Default::default()
-            })
-            .into_response(),
-            (None, None, None, None, None) => {
-                StatusCode::OK.with_body("Milk withdrawn\n").into_response()
-            }
-            _ => StatusCode::BAD_REQUEST.into_response(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl IntoResponse for Conversion {
+    fn into_response(self) -> Response {
+        if self.has_body.is_none() {
+            return StatusCode::OK.with_body("Milk withdrawn\n").into_response();
+        }
+        self.converted().map_or_else(
+            || StatusCode::BAD_REQUEST.into_response(),
+            |conversion| Json(conversion).into_response(),
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Quantity {
+    Volume,
+    Mass,
+    Temperature,
+}
+
+impl std::fmt::Display for Quantity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Quantity::Volume => "volume",
+            Quantity::Mass => "mass",
+            Quantity::Temperature => "temperature",
+        })
+    }
+}
+
+/// A unit is converted to the base unit of its quantity (liter, kilogram, kelvin) with
+/// `(value + offset) * scale`, which covers both linear and temperature scales.
+struct Unit {
+    names: &'static [&'static str],
+    quantity: Quantity,
+    scale: f64,
+    offset: f64,
+}
+
+const fn linear(names: &'static [&'static str], quantity: Quantity, scale: f64) -> Unit {
+    Unit {
+        names,
+        quantity,
+        scale,
+        offset: 0.0,
+    }
+}
+
+// Unqualified gallons are US gallons and unqualified pints are imperial pints, matching the
+// original field-based conversions.
+#[rustfmt::skip]
+const UNITS: &[Unit] = &[
+    linear(&["l", "liter", "liters", "litre", "litres"], Quantity::Volume, 1.0),
+    linear(&["ml", "milliliter", "milliliters", "millilitre", "millilitres"], Quantity::Volume, 0.001),
+    linear(&["m3", "cubic_meter", "cubic_meters", "cubic_metre", "cubic_metres"], Quantity::Volume, 1000.0),
+    linear(&["gal", "gallon", "gallons", "us_gallon", "us_gallons"], Quantity::Volume, 3.785_411_784),
+    linear(&["imperial_gallon", "imperial_gallons"], Quantity::Volume, 4.546_09),
+    linear(&["us_quart", "us_quarts"], Quantity::Volume, 0.946_352_946),
+    linear(&["imperial_quart", "imperial_quarts"], Quantity::Volume, 1.136_522_5),
+    linear(&["pt", "pint", "pints", "imperial_pint", "imperial_pints"], Quantity::Volume, 0.568_261_25),
+    linear(&["us_pint", "us_pints"], Quantity::Volume, 0.473_176_473),
+    linear(&["cup", "cups", "us_cup", "us_cups"], Quantity::Volume, 0.236_588_236_5),
+    linear(&["fl_oz", "us_fl_oz"], Quantity::Volume, 0.029_573_529_562_5),
+    linear(&["imperial_fl_oz"], Quantity::Volume, 0.028_413_062_5),
+    linear(&["mg", "milligram", "milligrams"], Quantity::Mass, 0.000_001),
+    linear(&["g", "gram", "grams"], Quantity::Mass, 0.001),
+    linear(&["kg", "kilogram", "kilograms"], Quantity::Mass, 1.0),
+    linear(&["t", "tonne", "tonnes"], Quantity::Mass, 1000.0),
+    linear(&["oz", "ounce", "ounces"], Quantity::Mass, 0.028_349_523_125),
+    linear(&["lb", "lbs", "pound", "pounds"], Quantity::Mass, 0.453_592_37),
+    linear(&["st", "stone", "stones"], Quantity::Mass, 6.350_293_18),
+    linear(&["us_ton", "us_tons", "short_ton", "short_tons"], Quantity::Mass, 907.184_74),
+    linear(&["imperial_ton", "imperial_tons", "long_ton", "long_tons"], Quantity::Mass, 1_016.046_908_8),
+    linear(&["k", "kelvin"], Quantity::Temperature, 1.0),
+    Unit { names: &["c", "celsius"], quantity: Quantity::Temperature, scale: 1.0, offset: 273.15 },
+    Unit { names: &["f", "fahrenheit"], quantity: Quantity::Temperature, scale: 5.0 / 9.0, offset: 459.67 },
+];
+
+fn unit(name: &str) -> Result<&'static Unit, ConversionError> {
+    let lowercase = name.to_lowercase();
+    UNITS
+        .iter()
+        .find(|unit| unit.names.contains(&lowercase.as_str()))
+        .ok_or_else(|| ConversionError::UnknownUnit(name.to_string()))
+}
+
+#[derive(Debug, PartialEq)]
+enum ConversionError {
+    UnknownUnit(String),
+    Incompatible(Quantity, Quantity),
+}
+
+impl std::fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownUnit(name) => write!(f, "Unknown unit: {name}"),
+            Self::Incompatible(from, to) => write!(f, "Cannot convert {from} to {to}"),
+        }
+    }
+}
+
+fn convert(value: f64, from: &str, to: &str) -> Result<f64, ConversionError> {
+    let (from, to) = (unit(from)?, unit(to)?);
+    if from.quantity != to.quantity {
+        return Err(ConversionError::Incompatible(from.quantity, to.quantity));
+    }
+    Ok((value + from.offset) * from.scale / to.scale - to.offset)
+}
+
+/// `{"from": "gallons", "to": "ml", "value": 3}`, answered with the same object plus `result`.
+#[derive(Deserialize, Serialize)]
+struct UnitConversion {
+    from: String,
+    to: String,
+    value: f64,
+    #[serde(default, skip_deserializing, skip_serializing_if = "Option::is_none")]
+    result: Option<f64>,
+}
+
+impl UnitConversion {
+    fn converted(self) -> Result<UnitConversion, ConversionError> {
+        let result = convert(self.value, &self.from, &self.to)?;
+        Ok(UnitConversion {
+            result: Some(result),
+            ..self
+        })
+    }
+}
+
+impl IntoResponse for UnitConversion {
+    fn into_response(self) -> Response {
+        match self.converted() {
+            Ok(conversion) => Json(conversion).into_response(),
+            Err(err) => StatusCode::BAD_REQUEST
+                .with_body(format!("{err}\n"))
+                .into_response(),
+        }
+    }
+}
+
+/// Body accepted by /9/milk: the unit form, or the original single-field form.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MilkRequest {
+    Units(UnitConversion),
+    Fields(Conversion),
+}
+
+impl MilkRequest {
+    /// The converted object, or why it could not be converted, for one entry of a batch.
+    fn converted(self) -> Result<serde_json::Value, String> {
+        match self {
+            Self::Units(conversion) => conversion
+                .converted()
+                .map_err(|err| err.to_string())
+                .and_then(|x| serde_json::to_value(x).map_err(|err| err.to_string())),
+            Self::Fields(conversion) => conversion
+                .converted()
+                .ok_or_else(|| {
+                    "Expected exactly one of liters, gallons, litres or pints".to_string()
+                })
+                .and_then(|x| serde_json::to_value(x).map_err(|err| err.to_string())),
+        }
+    }
+}
+
+/// Conversions paid for by one liter of milk in a batch request.
+const BATCH_CONVERSIONS_PER_LITER: usize = 100;
+/// Largest batch accepted, which costs a full bucket at the default capacity.
+const MAX_BATCH: usize = MAX_LITERS as usize * BATCH_CONVERSIONS_PER_LITER;
+
+/// Liters of milk a batch costs: one per started group of `BATCH_CONVERSIONS_PER_LITER`
+/// entries, and at least one like any other request.
+fn batch_cost(len: usize) -> u32 {
+    u32::try_from(len.div_ceil(BATCH_CONVERSIONS_PER_LITER).max(1)).unwrap_or(u32::MAX)
+}
+
+/// Converts every entry of a batch independently, reporting per-entry errors as
+/// `{"error": "..."}` in place of the result.
+fn convert_batch(entries: Vec<serde_json::Value>) -> Vec<serde_json::Value> {
+    entries
+        .into_iter()
+        .map(|entry| {
+            serde_json::from_value::<MilkRequest>(entry)
+                .map_err(|err| err.to_string())
+                .and_then(MilkRequest::converted)
+                .unwrap_or_else(|err| serde_json::json!({ "error": err }))
+        })
+        .collect()
+}
+
+impl IntoResponse for MilkRequest {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Units(conversion) => conversion.into_response(),
+            Self::Fields(conversion) => conversion.into_response(),
         }
     }
 }
 
+async fn acquire(rate_limit: &RateLimiter, liters: u32) -> Option<Response> {
+    match rate_limit.try_acquire(liters).await {
+        Ok(true) => None,
+        Ok(false) => Some(
+            StatusCode::TOO_MANY_REQUESTS
+                .with_body("No milk available\n")
+                .into_response(),
+        ),
+        Err(err) => Some(db_error(&err).into_response()),
+    }
+}
+
 #[handler]
 async fn milk(
-    Data(rate_limit): Data<&Arc<Mutex<RateLimiter>>>,
+    Data(rate_limit): Data<&Arc<RateLimiter>>,
     req: &Request,
     body: poem::Body,
 ) -> Response {
-    if !rate_limit.lock().unwrap().try_acquire(1) {
-        return StatusCode::TOO_MANY_REQUESTS
-            .with_body("No milk available\n")
-            .into_response();
+    let is_json = req.headers().get(CONTENT_TYPE).is_some_and(|ct| {
+        ct.to_str()
+            .map_or(true, |ct| ct.starts_with("application/json"))
+    });
+    if !is_json {
+        return acquire(rate_limit, 1)
+            .await
+            .unwrap_or_else(|| Conversion::default().into_response());
     }
 
-    if let Some(ct) = req.headers().get(CONTENT_TYPE) {
-        if let Ok(ct) = ct.to_str() {
-            if !ct.starts_with("application/json") {
-                return Conversion::default().into_response();
+    let mut body = RequestBody::new(body);
+    let body = match Json::<serde_json::Value>::from_request(req, &mut body).await {
+        Ok(Json(body)) => body,
+        Err(err) => {
+            return acquire(rate_limit, 1)
+                .await
+                .unwrap_or_else(|| err.into_response())
+        }
+    };
+
+    match body {
+        serde_json::Value::Array(entries) => {
+            if entries.len() > MAX_BATCH {
+                return StatusCode::PAYLOAD_TOO_LARGE
+                    .with_body(format!("At most {MAX_BATCH} conversions per batch\n"))
+                    .into_response();
+            }
+            if let Some(res) = acquire(rate_limit, batch_cost(entries.len())).await {
+                return res;
+            }
+            Json(convert_batch(entries)).into_response()
+        }
+        body => {
+            if let Some(res) = acquire(rate_limit, 1).await {
+                return res;
             }
+            serde_json::from_value::<MilkRequest>(body).map_or_else(
+                |err| {
+                    StatusCode::BAD_REQUEST
+                        .with_body(format!("{err}\n"))
+                        .into_response()
+                },
+                IntoResponse::into_response,
+            )
         }
-    } else {
-        return Conversion::default().into_response();
     }
-    let mut body = RequestBody::new(body);
-    Json::<Conversion>::from_request(req, &mut body)
-        .await
-        .map_or_else(Error::into_response, |Json(x)| x.into_response())
+}
+
+#[derive(Deserialize)]
+struct Liters {
+    liters: Option<u32>,
 }
 
 #[handler]
-fn refill(Data(rate_limit): Data<&Arc<Mutex<RateLimiter>>>) {
-    *rate_limit.lock().unwrap() = rate_limit_builder();
+async fn refill(
+    Data(rate_limit): Data<&Arc<RateLimiter>>,
+    Query(query): Query<Liters>,
+) -> StatusCode {
+    match query.liters {
+        Some(liters) => rate_limit.add(liters).await,
+        None => rate_limit.refill().await,
+    }
+    .map_or_else(|err| db_error(&err), |()| StatusCode::OK)
 }
 
-fn rate_limit_builder() -> RateLimiter {
-    RateLimiter::builder()
-        .max(MAX_LITERS)
-        .interval(Duration::from_secs(1))
-        .initial(MAX_LITERS)
-        .refill(1)
-        .build()
+#[handler]
+async fn resize(
+    Data(rate_limit): Data<&Arc<RateLimiter>>,
+    Query(query): Query<Liters>,
+) -> StatusCode {
+    let Some(liters) = query.liters else {
+        return StatusCode::BAD_REQUEST;
+    };
+    rate_limit
+        .set_capacity(liters)
+        .await
+        .map_or_else(|err| db_error(&err), |()| StatusCode::OK)
 }
 
-pub(crate) fn route() -> impl Endpoint {
-    // Mutex is not needed, but as the RateLimiter library does not have a manual refilling,
-    // we need to replace the whole object:
-    // https://github.com/udoprog/leaky-bucket/issues/17
-    let rate_limit = Arc::new(Mutex::new(rate_limit_builder()));
+pub(crate) fn route(pool: sqlx::PgPool) -> impl Endpoint {
+    let rate_limit = match std::env::var("MILK_RATE_LIMITER").as_deref() {
+        Ok("postgres") => RateLimiter::Postgres(PgBucket::new(
+            pool,
+            "milk",
+            MAX_LITERS,
+            1,
+            Duration::from_secs(1),
+        )),
+        _ => RateLimiter::Memory(TokenBucket::new(MAX_LITERS, 1, Duration::from_secs(1))),
+    };
     Route::new()
         .at("/milk", post(milk))
         .at("/refill", post(refill))
-        .with(AddData::new(rate_limit))
+        .at("/capacity", post(resize))
+        .with(AddData::new(Arc::new(rate_limit)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_convert_units() {
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-9;
+        assert!(close(
+            convert(3.0, "gallons", "ml").unwrap(),
+            11_356.235_352
+        ));
+        assert!(close(
+            convert(1.0, "imperial_gallons", "Pints").unwrap(),
+            8.0
+        ));
+        assert!(close(convert(2.0, "lb", "g").unwrap(), 907.184_74));
+        assert!(close(convert(100.0, "celsius", "f").unwrap(), 212.0));
+        assert!(close(convert(-40.0, "f", "c").unwrap(), -40.0));
+        assert!(close(convert(0.0, "c", "kelvin").unwrap(), 273.15));
+        assert_eq!(
+            convert(1.0, "kg", "liters"),
+            Err(ConversionError::Incompatible(
+                Quantity::Mass,
+                Quantity::Volume
+            ))
+        );
+        assert_eq!(
+            convert(1.0, "cubits", "liters"),
+            Err(ConversionError::UnknownUnit("cubits".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_batch_cost() {
+        assert_eq!(batch_cost(0), 1);
+        assert_eq!(batch_cost(1), 1);
+        assert_eq!(batch_cost(100), 1);
+        assert_eq!(batch_cost(101), 2);
+        assert_eq!(batch_cost(MAX_BATCH), MAX_LITERS);
+    }
+
+    #[test]
+    fn test_concurrent_acquire_never_overdraws() {
+        let bucket = Arc::new(TokenBucket::new(1000, 1, Duration::from_secs(3600)));
+        let acquired = Arc::new(AtomicUsize::new(0));
+        let handles = (0..8)
+            .map(|_| {
+                let bucket = bucket.clone();
+                let acquired = acquired.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..500 {
+                        if bucket.try_acquire(1) {
+                            acquired.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        handles.into_iter().for_each(|h| h.join().unwrap());
+
+        assert_eq!(acquired.load(Ordering::Relaxed), 1000);
+        assert!(!bucket.try_acquire(1));
+    }
+
+    #[test]
+    fn test_partial_refill_and_capacity() {
+        let bucket = TokenBucket::new(5, 1, Duration::from_secs(3600));
+        assert!(bucket.try_acquire(5));
+        bucket.add(2);
+        assert!(bucket.try_acquire(2));
+        assert!(!bucket.try_acquire(1));
+
+        bucket.add(100);
+        assert!(!bucket.try_acquire(6));
+        bucket.set_capacity(3);
+        assert!(!bucket.try_acquire(4));
+        assert!(bucket.try_acquire(3));
+
+        bucket.set_capacity(10);
+        bucket.refill();
+        assert!(bucket.try_acquire(10));
+    }
+
+    #[test]
+    fn test_refills_over_time() {
+        let bucket = TokenBucket::new(2, 1, Duration::from_millis(20));
+        assert!(bucket.try_acquire(2));
+        assert!(!bucket.try_acquire(1));
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(bucket.try_acquire(2));
+    }
+
+    /// Compares against the previous `Mutex<leaky_bucket::RateLimiter>` setup.
+    /// Run with `cargo test --release -- --ignored --nocapture bench_`.
+    #[test]
+    #[ignore]
+    fn bench_concurrent_try_acquire() {
+        const THREADS: usize = 8;
+        const ITERATIONS: usize = 200_000;
+
+        fn run(f: impl Fn() -> bool + Send + Sync + 'static) -> Duration {
+            let f = Arc::new(f);
+            let start = Instant::now();
+            let handles = (0..THREADS)
+                .map(|_| {
+                    let f = f.clone();
+                    std::thread::spawn(move || {
+                        for _ in 0..ITERATIONS {
+                            std::hint::black_box(f());
+                        }
+                    })
+                })
+                .collect::<Vec<_>>();
+            handles.into_iter().for_each(|h| h.join().unwrap());
+            start.elapsed()
+        }
+
+        let bucket = TokenBucket::new(MAX_LITERS, 1, Duration::from_secs(1));
+        let atomic = run(move || bucket.try_acquire(1));
+
+        let limiter = Mutex::new(
+            leaky_bucket::RateLimiter::builder()
+                .max(MAX_LITERS as usize)
+                .interval(Duration::from_secs(1))
+                .initial(MAX_LITERS as usize)
+                .refill(1)
+                .build(),
+        );
+        let mutex = run(move || limiter.lock().unwrap().try_acquire(1));
+
+        println!(
+            "{THREADS} threads x {ITERATIONS} try_acquire: atomic {atomic:?}, mutex {mutex:?}"
+        );
+    }
 }
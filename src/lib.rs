@@ -29,10 +29,10 @@ pub fn main_router(pool: sqlx::PgPool) -> Route {
             Api,
             day1::Api,
             day_2::Api,
-            day_5::Api,
+            day_5::Api::new(),
             day_16::Api::new(),
-            day_19::Api::new(pool),
-            day_23::Api,
+            day_19::Api::new(pool.clone()),
+            day_23::Api::new(),
         ),
         "Shuttling-cch24",
         "1.0",
@@ -40,8 +40,8 @@ pub fn main_router(pool: sqlx::PgPool) -> Route {
     let swagger_ui = oapi.swagger_ui();
     Route::new()
         .nest("/", oapi)
-        .nest("/9", day_9::route())
-        .nest("/12", day_12::route())
+        .nest("/9", day_9::route(pool.clone()))
+        .nest("/12", day_12::route(pool))
         .nest("/swagger", swagger_ui)
         .nest("/assets", StaticFilesEndpoint::new("assets"))
 }
@@ -1,6 +1,3 @@
-mod day1;
-mod day_2;
-
 use shuttle_poem::ShuttlePoem;
 use shuttlings_cch24::main_router;
 
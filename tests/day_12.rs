@@ -0,0 +1,925 @@
+mod helper;
+use futures_util::{SinkExt, StreamExt};
+use helper::main_router;
+use poem::http::StatusCode;
+use poem::listener::{Acceptor, Listener, TcpListener};
+use poem::test::TestClient;
+use std::sync::Arc;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::WebSocketStream;
+
+const EMPTY: &str = "⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜⬜⬜⬜⬜⬜
+";
+
+#[tokio::test]
+async fn test_day12_task1() {
+    let cli = TestClient::new(main_router());
+    let res = cli.get("/12/board").send().await;
+    res.assert_status_is_ok();
+    res.assert_text(EMPTY).await;
+    let res = cli.post("/12/reset").send().await;
+    res.assert_status_is_ok();
+    res.assert_text(EMPTY).await;
+}
+
+#[tokio::test]
+async fn test_day12_task2() {
+    let cli = TestClient::new(main_router());
+    for _ in 0..3 {
+        cli.post("/12/place/cookie/1")
+            .send()
+            .await
+            .assert_status_is_ok();
+    }
+    let res = cli.post("/12/place/cookie/1").send().await;
+    res.assert_status_is_ok();
+    res.assert_text(
+        "⬜🍪⬛⬛⬛⬜
+⬜🍪⬛⬛⬛⬜
+⬜🍪⬛⬛⬛⬜
+⬜🍪⬛⬛⬛⬜
+⬜⬜⬜⬜⬜⬜
+🍪 wins!
+",
+    )
+    .await;
+    cli.post("/12/place/milk/2")
+        .send()
+        .await
+        .assert_status(StatusCode::SERVICE_UNAVAILABLE);
+
+    cli.post("/12/reset").send().await.assert_status_is_ok();
+    cli.post("/12/place/milk/5")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    cli.post("/12/place/milk/0")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_day12_task3() {
+    let cli = TestClient::new(main_router());
+    let boards = [
+        "⬜🍪🍪🍪🍪⬜
+⬜🥛🍪🍪🥛⬜
+⬜🥛🥛🥛🥛⬜
+⬜🍪🥛🍪🥛⬜
+⬜⬜⬜⬜⬜⬜
+🍪 wins!
+",
+        "⬜🍪🥛🍪🍪⬜
+⬜🥛🍪🥛🍪⬜
+⬜🥛🍪🍪🍪⬜
+⬜🍪🥛🥛🥛⬜
+⬜⬜⬜⬜⬜⬜
+No winner.
+",
+        "⬜🍪🍪🥛🍪⬜
+⬜🍪🥛🍪🍪⬜
+⬜🥛🍪🍪🥛⬜
+⬜🍪🥛🍪🍪⬜
+⬜⬜⬜⬜⬜⬜
+🍪 wins!
+",
+        "⬜🥛🍪🍪🥛⬜
+⬜🥛🍪🍪🍪⬜
+⬜🍪🥛🥛🥛⬜
+⬜🍪🥛🍪🥛⬜
+⬜⬜⬜⬜⬜⬜
+No winner.
+",
+    ];
+    for board in boards {
+        let res = cli.get("/12/random-board").send().await;
+        res.assert_status_is_ok();
+        res.assert_text(board).await;
+    }
+    cli.post("/12/reset").send().await.assert_status_is_ok();
+    let res = cli.get("/12/random-board").send().await;
+    res.assert_text(boards[0]).await;
+}
+
+#[tokio::test]
+async fn test_day12_board_size() {
+    let cli = TestClient::new(main_router());
+    let res = cli
+        .post("/12/reset")
+        .query("width", &7)
+        .query("height", &6)
+        .send()
+        .await;
+    res.assert_status_is_ok();
+    res.assert_text("⬜⬛⬛⬛⬛⬛⬛⬛⬜\n".repeat(6) + "⬜⬜⬜⬜⬜⬜⬜⬜⬜\n")
+        .await;
+
+    cli.post("/12/place/milk/7")
+        .send()
+        .await
+        .assert_status_is_ok();
+    cli.post("/12/place/milk/8")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+
+    // A plain reset keeps the configured size.
+    let res = cli.post("/12/reset").send().await;
+    res.assert_text("⬜⬛⬛⬛⬛⬛⬛⬛⬜\n".repeat(6) + "⬜⬜⬜⬜⬜⬜⬜⬜⬜\n")
+        .await;
+
+    cli.post("/12/reset")
+        .query("connect", &8)
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    cli.post("/12/reset")
+        .query("width", &17)
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_day12_connect_three() {
+    let cli = TestClient::new(main_router());
+    cli.post("/12/reset")
+        .query("width", &5)
+        .query("height", &3)
+        .query("connect", &3)
+        .send()
+        .await
+        .assert_status_is_ok();
+    for column in 3..5 {
+        cli.post(format!("/12/place/milk/{column}"))
+            .send()
+            .await
+            .assert_status_is_ok();
+    }
+    let res = cli.post("/12/place/milk/5").send().await;
+    res.assert_status_is_ok();
+    res.assert_text(
+        "⬜⬛⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬛⬜
+⬜⬛⬛🥛🥛🥛⬜
+⬜⬜⬜⬜⬜⬜⬜
+🥛 wins!
+",
+    )
+    .await;
+}
+
+async fn create_game(cli: &TestClient<impl poem::Endpoint>) -> String {
+    let res = cli.post("/12/games").send().await;
+    res.assert_status(StatusCode::CREATED);
+    let json = res.json().await;
+    json.value()
+        .object()
+        .get_opt("id")
+        .unwrap()
+        .string()
+        .to_string()
+}
+
+async fn play<E: poem::Endpoint>(
+    cli: &TestClient<E>,
+    game: &str,
+    player: &str,
+    team: &str,
+    column: usize,
+) -> poem::test::TestResponse {
+    cli.post(format!("/12/games/{game}/place/{team}/{column}"))
+        .header("Cookie", format!("player={player}"))
+        .send()
+        .await
+}
+
+#[tokio::test]
+async fn test_day12_games() {
+    let cli = TestClient::new(main_router());
+    let first = create_game(&cli).await;
+    let second = create_game(&cli).await;
+
+    for _ in 0..3 {
+        play(&cli, &first, "alice", "cookie", 1)
+            .await
+            .assert_status_is_ok();
+        play(&cli, &first, "bob", "milk", 2)
+            .await
+            .assert_status_is_ok();
+    }
+    play(&cli, &first, "alice", "cookie", 1)
+        .await
+        .assert_status_is_ok();
+    play(&cli, &first, "bob", "milk", 2)
+        .await
+        .assert_status(StatusCode::SERVICE_UNAVAILABLE);
+
+    // Each game and the original board are independent.
+    cli.get(format!("/12/games/{second}/board"))
+        .send()
+        .await
+        .assert_text(EMPTY)
+        .await;
+    cli.get("/12/board").send().await.assert_text(EMPTY).await;
+
+    let res = cli.get("/12/games").send().await;
+    res.assert_status_is_ok();
+    let json = res.json().await;
+    let games = json.value().array();
+    games.assert_len(2);
+    let finished = games
+        .iter()
+        .find(|game| game.object().get("id").string() == first)
+        .unwrap();
+    finished.object().get("finished").assert_bool(true);
+
+    cli.delete(format!("/12/games/{first}"))
+        .header("Cookie", "player=bob")
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+    cli.get(format!("/12/games/{first}/board"))
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+    cli.delete(format!("/12/games/{first}"))
+        .header("Cookie", "player=bob")
+        .send()
+        .await
+        .assert_status(StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_day12_game_size() {
+    let cli = TestClient::new(main_router());
+    let res = cli
+        .post("/12/games")
+        .query("width", &7)
+        .query("height", &6)
+        .send()
+        .await;
+    res.assert_status(StatusCode::CREATED);
+    let location = res.0.headers()["Location"].to_str().unwrap().to_string();
+    let json = res.json().await;
+    let game = json.value().object();
+    game.get("width").assert_i64(7);
+    game.get("connect").assert_i64(4);
+    let id = game.get("id").string();
+    assert_eq!(location, format!("/12/games/{id}"));
+
+    cli.post("/12/games")
+        .query("height", &0)
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_day12_turns() {
+    let cli = TestClient::new(main_router());
+    let game = create_game(&cli).await;
+
+    let res = play(&cli, &game, "alice", "milk", 1).await;
+    res.assert_status(StatusCode::CONFLICT);
+    res.assert_text("It is 🍪's turn\n").await;
+
+    play(&cli, &game, "alice", "cookie", 1)
+        .await
+        .assert_status_is_ok();
+    play(&cli, &game, "alice", "cookie", 1)
+        .await
+        .assert_status(StatusCode::CONFLICT);
+    let res = play(&cli, &game, "alice", "milk", 1).await;
+    res.assert_status(StatusCode::FORBIDDEN);
+    res.assert_text("You are playing 🍪\n").await;
+
+    let res = cli.get(format!("/12/games/{game}/state")).send().await;
+    res.assert_json(serde_json::json!({
+        "whose_turn": "milk",
+        "legal_columns": [1, 2, 3, 4],
+        "seats": {"cookie": true, "milk": false},
+    }))
+    .await;
+
+    play(&cli, &game, "bob", "milk", 2)
+        .await
+        .assert_status_is_ok();
+    let res = play(&cli, &game, "carol", "cookie", 2).await;
+    res.assert_status(StatusCode::FORBIDDEN);
+    res.assert_text("🍪 is played by someone else\n").await;
+    play(&cli, &game, "alice", "cookie", 3)
+        .await
+        .assert_status_is_ok();
+
+    // The original board is still free play.
+    for _ in 0..2 {
+        cli.post("/12/place/cookie/1")
+            .send()
+            .await
+            .assert_status_is_ok();
+    }
+}
+
+#[tokio::test]
+async fn test_day12_player_identity() {
+    let cli = TestClient::new(main_router());
+    let game = create_game(&cli).await;
+
+    let res = cli
+        .post(format!("/12/games/{game}/place/cookie/1"))
+        .send()
+        .await;
+    res.assert_status_is_ok();
+    let cookie = res.0.headers()["Set-Cookie"].to_str().unwrap().to_string();
+    assert!(cookie.starts_with("player="));
+
+    play(&cli, &game, "bob", "milk", 1)
+        .await
+        .assert_status_is_ok();
+    play(&cli, &game, "bob", "cookie", 1)
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+    play(&cli, &game, "bob", "milk", 1)
+        .await
+        .assert_status(StatusCode::CONFLICT);
+
+    // Without `PLAYER_TOKEN_SECRET`, no bearer token is valid, not even one signed with the
+    // day 16 secret.
+    let token = jsonwebtoken::encode(
+        &jsonwebtoken::Header::default(),
+        &serde_json::json!({"sub": "bob"}),
+        &jsonwebtoken::EncodingKey::from_secret(b"secret"),
+    )
+    .unwrap();
+    cli.post(format!("/12/games/{game}/place/milk/1"))
+        .header("Authorization", format!("Bearer {token}"))
+        .send()
+        .await
+        .assert_status(StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_day12_players_only() {
+    let cli = TestClient::new(main_router());
+    let game = create_game(&cli).await;
+    play(&cli, &game, "alice", "cookie", 1)
+        .await
+        .assert_status_is_ok();
+
+    cli.post(format!("/12/games/{game}/reset"))
+        .header("Cookie", "player=carol")
+        .send()
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+    cli.get(format!("/12/games/{game}/random-board"))
+        .header("Cookie", "player=carol")
+        .send()
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+    cli.delete(format!("/12/games/{game}"))
+        .header("Cookie", "player=carol")
+        .send()
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+
+    cli.post(format!("/12/games/{game}/reset"))
+        .header("Cookie", "player=alice")
+        .send()
+        .await
+        .assert_text(EMPTY)
+        .await;
+    cli.get(format!("/12/games/{game}/random-board"))
+        .header("Cookie", "player=alice")
+        .send()
+        .await
+        .assert_status_is_ok();
+    cli.delete(format!("/12/games/{game}"))
+        .header("Cookie", "player=alice")
+        .send()
+        .await
+        .assert_status(StatusCode::NO_CONTENT);
+}
+
+#[tokio::test]
+async fn test_day12_full_column() {
+    let cli = TestClient::new(main_router());
+    cli.post("/12/reset")
+        .query("height", &2)
+        .send()
+        .await
+        .assert_status_is_ok();
+    cli.post("/12/place/cookie/1")
+        .send()
+        .await
+        .assert_status_is_ok();
+    cli.post("/12/place/milk/1")
+        .send()
+        .await
+        .assert_status_is_ok();
+    let res = cli.post("/12/place/cookie/1").send().await;
+    res.assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    res.assert_text("Column 1 is full\n").await;
+
+    let res = cli
+        .post("/12/games")
+        .query("height", &1)
+        .query("connect", &3)
+        .send()
+        .await;
+    let json = res.json().await;
+    let game = json.value().object().get("id").string().to_string();
+    play(&cli, &game, "alice", "cookie", 2)
+        .await
+        .assert_status_is_ok();
+    play(&cli, &game, "bob", "milk", 2)
+        .await
+        .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    let res = cli.get(format!("/12/games/{game}/state")).send().await;
+    res.assert_json(serde_json::json!({
+        "whose_turn": "milk",
+        "legal_columns": [1, 3, 4],
+        "seats": {"cookie": true, "milk": false},
+    }))
+    .await;
+}
+
+#[tokio::test]
+async fn test_day12_json_board() {
+    let cli = TestClient::new(main_router());
+    cli.post("/12/place/cookie/2")
+        .send()
+        .await
+        .assert_status_is_ok();
+    let res = cli
+        .post("/12/place/milk/2")
+        .header("Accept", "application/json")
+        .send()
+        .await;
+    res.assert_status_is_ok();
+    res.assert_content_type("application/json; charset=utf-8");
+    res.assert_json(serde_json::json!({
+        "width": 4,
+        "height": 4,
+        "connect": 4,
+        "grid": [
+            [null, null, null, null],
+            [null, null, null, null],
+            [null, "milk", null, null],
+            [null, "cookie", null, null],
+        ],
+        "finished": false,
+        "winner": null,
+        "moves": 2,
+        "whose_turn": "cookie",
+        "legal_columns": [1, 2, 3, 4],
+    }))
+    .await;
+
+    // Text stays the default, and wins when preferred.
+    cli.get("/12/board")
+        .send()
+        .await
+        .assert_content_type("text/plain; charset=utf-8");
+    cli.get("/12/board")
+        .header("Accept", "text/plain, application/json;q=0.5")
+        .send()
+        .await
+        .assert_content_type("text/plain; charset=utf-8");
+
+    let res = cli
+        .get("/12/random-board")
+        .header("Accept", "application/json")
+        .send()
+        .await;
+    let json = res.json().await;
+    let board = json.value().object();
+    board.get("finished").assert_bool(true);
+    board.get("winner").assert_string("cookie");
+    board.get("moves").assert_i64(16);
+    board.get("whose_turn").assert_null();
+    board.get("legal_columns").array().assert_len(0);
+}
+
+#[tokio::test]
+async fn test_day12_json_game() {
+    let cli = TestClient::new(main_router());
+    let game = create_game(&cli).await;
+    let res = cli
+        .get(format!("/12/games/{game}/board"))
+        .header("Accept", "application/json")
+        .send()
+        .await;
+    res.assert_status_is_ok();
+    let json = res.json().await;
+    json.value().object().get("moves").assert_i64(0);
+
+    let res = cli
+        .post(format!("/12/games/{game}/place/cookie/9"))
+        .header("Accept", "application/json")
+        .send()
+        .await;
+    res.assert_status(StatusCode::BAD_REQUEST);
+    res.assert_content_type("application/json; charset=utf-8");
+}
+
+#[tokio::test]
+async fn test_day12_computer() {
+    let cli = TestClient::new(main_router());
+    for column in 1..4 {
+        cli.post(format!("/12/place/milk/{column}"))
+            .send()
+            .await
+            .assert_status_is_ok();
+    }
+    let res = cli
+        .post("/12/ai/cookie")
+        .query("difficulty", &"hard")
+        .send()
+        .await;
+    res.assert_status_is_ok();
+    res.assert_text(
+        "⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜🥛🥛🥛🍪⬜
+⬜⬜⬜⬜⬜⬜
+",
+    )
+    .await;
+    cli.post("/12/ai/cookie")
+        .query("difficulty", &"impossible")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_day12_computer_game() {
+    let cli = TestClient::new(main_router());
+    let game = create_game(&cli).await;
+
+    cli.post(format!("/12/games/{game}/ai/milk"))
+        .send()
+        .await
+        .assert_status(StatusCode::CONFLICT);
+    play(&cli, &game, "alice", "cookie", 1)
+        .await
+        .assert_status_is_ok();
+    cli.post(format!("/12/games/{game}/ai/cookie"))
+        .send()
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+    let res = cli
+        .post(format!("/12/games/{game}/ai/milk"))
+        .header("Accept", "application/json")
+        .send()
+        .await;
+    res.assert_status_is_ok();
+    let json = res.json().await;
+    let board = json.value().object();
+    board.get("moves").assert_i64(2);
+    board.get("whose_turn").assert_string("cookie");
+
+    // The computer now holds milk.
+    play(&cli, &game, "bob", "milk", 1)
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_day12_undo() {
+    let cli = TestClient::new(main_router());
+    cli.post("/12/undo")
+        .send()
+        .await
+        .assert_status(StatusCode::CONFLICT);
+    cli.post("/12/place/cookie/1")
+        .send()
+        .await
+        .assert_status_is_ok();
+    let res = cli.post("/12/undo").send().await;
+    res.assert_status_is_ok();
+    res.assert_text(EMPTY).await;
+
+    cli.get("/12/random-board")
+        .send()
+        .await
+        .assert_status_is_ok();
+    cli.post("/12/undo")
+        .send()
+        .await
+        .assert_status(StatusCode::CONFLICT);
+    cli.get("/12/export")
+        .send()
+        .await
+        .assert_status(StatusCode::CONFLICT);
+    cli.post("/12/reset").send().await.assert_status_is_ok();
+
+    let game = create_game(&cli).await;
+    play(&cli, &game, "alice", "cookie", 1)
+        .await
+        .assert_status_is_ok();
+    cli.post(format!("/12/games/{game}/undo"))
+        .header("Cookie", "player=bob")
+        .send()
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+    let res = cli
+        .post(format!("/12/games/{game}/undo"))
+        .header("Cookie", "player=alice")
+        .send()
+        .await;
+    res.assert_status_is_ok();
+    res.assert_text(EMPTY).await;
+    play(&cli, &game, "alice", "cookie", 2)
+        .await
+        .assert_status_is_ok();
+    play(&cli, &game, "bob", "milk", 3)
+        .await
+        .assert_status_is_ok();
+
+    // Only the last move can be taken back, by whoever made it.
+    cli.post(format!("/12/games/{game}/undo"))
+        .header("Cookie", "player=alice")
+        .send()
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+    cli.post(format!("/12/games/{game}/undo"))
+        .header("Cookie", "player=bob")
+        .send()
+        .await
+        .assert_status_is_ok();
+
+    // Finished games stay finished.
+    for _ in 0..3 {
+        play(&cli, &game, "bob", "milk", 3)
+            .await
+            .assert_status_is_ok();
+        play(&cli, &game, "alice", "cookie", 2)
+            .await
+            .assert_status_is_ok();
+    }
+    cli.post(format!("/12/games/{game}/undo"))
+        .header("Cookie", "player=alice")
+        .send()
+        .await
+        .assert_status(StatusCode::CONFLICT);
+}
+
+#[tokio::test]
+async fn test_day12_replay() {
+    let cli = TestClient::new(main_router());
+    let game = create_game(&cli).await;
+    play(&cli, &game, "alice", "cookie", 1)
+        .await
+        .assert_status_is_ok();
+    play(&cli, &game, "bob", "milk", 2)
+        .await
+        .assert_status_is_ok();
+
+    let res = cli
+        .get(format!("/12/games/{game}/replay"))
+        .header("Accept", "application/json")
+        .send()
+        .await;
+    res.assert_status_is_ok();
+    let json = res.json().await;
+    let states = json.value().array();
+    states.assert_len(3);
+    for (i, state) in states.iter().enumerate() {
+        state.object().get("moves").assert_i64(i as i64);
+    }
+
+    let res = cli.get(format!("/12/games/{game}/replay")).send().await;
+    res.assert_status_is_ok();
+    res.assert_text(format!(
+        "{EMPTY}
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜🍪⬛⬛⬛⬜
+⬜⬜⬜⬜⬜⬜
+
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜⬛⬛⬛⬛⬜
+⬜🍪🥛⬛⬛⬜
+⬜⬜⬜⬜⬜⬜
+"
+    ))
+    .await;
+}
+
+#[tokio::test]
+async fn test_day12_import_export() {
+    let cli = TestClient::new(main_router());
+    let res = cli.post("/12/import").body("4x4c4:c1m2c1").send().await;
+    res.assert_status_is_ok();
+    let res = cli.get("/12/export").send().await;
+    res.assert_status_is_ok();
+    res.assert_text("4x4c4:c1m2c1").await;
+    // Empty cells are not a team, and must not end up in the history.
+    cli.post("/12/place/Empty/1")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    let res = cli.get("/12/export").send().await;
+    res.assert_text("4x4c4:c1m2c1").await;
+    cli.post("/12/import")
+        .body("4x4c4:c9")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    cli.post("/12/reset").send().await.assert_status_is_ok();
+
+    cli.post("/12/games/import")
+        .body("7x6c4:c4c4")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    let res = cli
+        .post("/12/games/import")
+        .body("7x6c4:c4m4c3")
+        .send()
+        .await;
+    res.assert_status(StatusCode::CREATED);
+    let json = res.json().await;
+    let game = json.value().object().get("id").string().to_string();
+    json.value().object().get("width").assert_i64(7);
+
+    let res = cli.get(format!("/12/games/{game}/export")).send().await;
+    res.assert_status_is_ok();
+    res.assert_text("7x6c4:c4m4c3").await;
+    // Milk is next, and the first player to move takes the seat.
+    play(&cli, &game, "bob", "cookie", 1)
+        .await
+        .assert_status(StatusCode::CONFLICT);
+    play(&cli, &game, "bob", "Empty", 1)
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    play(&cli, &game, "bob", "milk", 1)
+        .await
+        .assert_status_is_ok();
+    let res = cli.get(format!("/12/games/{game}/export")).send().await;
+    res.assert_text("7x6c4:c4m4c3m1").await;
+}
+
+/// Serves `app` on a free local port, since the test client cannot upgrade connections.
+async fn serve(app: impl poem::Endpoint + 'static) -> String {
+    let acceptor = TcpListener::bind("127.0.0.1:0")
+        .into_acceptor()
+        .await
+        .unwrap();
+    let addr = acceptor.local_addr()[0]
+        .as_socket_addr()
+        .unwrap()
+        .to_string();
+    tokio::spawn(poem::Server::new_with_acceptor(acceptor).run(app));
+    addr
+}
+
+async fn connect(addr: &str, path: &str, player: &str) -> WebSocketStream<TcpStream> {
+    let mut request = format!("ws://{addr}{path}").into_client_request().unwrap();
+    request
+        .headers_mut()
+        .insert("Cookie", format!("player={player}").parse().unwrap());
+    let stream = TcpStream::connect(addr).await.unwrap();
+    tokio_tungstenite::client_async(request, stream)
+        .await
+        .unwrap()
+        .0
+}
+
+async fn receive(socket: &mut WebSocketStream<TcpStream>) -> serde_json::Value {
+    match socket.next().await {
+        Some(Ok(Message::Text(text))) => serde_json::from_str(&text).unwrap(),
+        message => panic!("expected a text message, got {message:?}"),
+    }
+}
+
+async fn send(socket: &mut WebSocketStream<TcpStream>, message: &str) {
+    socket
+        .send(Message::Text(message.to_string()))
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn test_day12_websocket() {
+    let app = Arc::new(main_router());
+    let addr = serve(app.clone()).await;
+    let cli = TestClient::new(app);
+    cli.get("/12/ws")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+
+    let mut alice = connect(&addr, "/12/ws", "alice").await;
+    let mut bob = connect(&addr, "/12/ws", "bob").await;
+    assert_eq!(receive(&mut alice).await["moves"], 0);
+    assert_eq!(receive(&mut bob).await["moves"], 0);
+
+    send(&mut alice, r#"{"team": "cookie", "column": 2}"#).await;
+    let board = receive(&mut bob).await;
+    assert_eq!(board["grid"][3][1], "cookie");
+    assert_eq!(receive(&mut alice).await, board);
+
+    send(&mut bob, r#"{"team": "milk", "column": 5}"#).await;
+    assert_eq!(receive(&mut bob).await["error"], "No column 5");
+    send(&mut bob, r#"{"team": "Empty", "column": 1}"#).await;
+    assert_eq!(
+        receive(&mut bob).await["error"],
+        "Tokens are cookie or milk"
+    );
+    send(&mut bob, "milk").await;
+    assert!(receive(&mut bob).await["error"].is_string());
+
+    // Changes made over HTTP are pushed too, refused ones are not.
+    cli.post("/12/place/milk/9").send().await;
+    cli.post("/12/reset").send().await.assert_status_is_ok();
+    assert_eq!(receive(&mut alice).await["moves"], 0);
+    assert_eq!(receive(&mut bob).await["moves"], 0);
+}
+
+#[tokio::test]
+async fn test_day12_websocket_game() {
+    let app = Arc::new(main_router());
+    let addr = serve(app.clone()).await;
+    let cli = TestClient::new(app);
+    let game = create_game(&cli).await;
+
+    let mut alice = connect(&addr, &format!("/12/games/{game}/ws"), "alice").await;
+    receive(&mut alice).await;
+    send(&mut alice, r#"{"team": "milk", "column": 1}"#).await;
+    assert_eq!(receive(&mut alice).await["error"], "It is 🍪's turn");
+    send(&mut alice, r#"{"team": "cookie", "column": 1}"#).await;
+    assert_eq!(receive(&mut alice).await["whose_turn"], "milk");
+
+    // Alice holds cookie, so bob's HTTP move for milk reaches her socket.
+    play(&cli, &game, "alice", "milk", 2)
+        .await
+        .assert_status(StatusCode::FORBIDDEN);
+    play(&cli, &game, "bob", "milk", 2)
+        .await
+        .assert_status_is_ok();
+    assert_eq!(receive(&mut alice).await["moves"], 2);
+
+    send(&mut alice, r#"{"team": "cookie", "column": 1}"#).await;
+    assert_eq!(receive(&mut alice).await["moves"], 3);
+}
+
+#[tokio::test]
+async fn test_day12_seeded_random_board() {
+    let cli = TestClient::new(main_router());
+    let res = cli.get("/12/random-board").query("seed", &42).send().await;
+    res.assert_status_is_ok();
+    res.assert_header("X-Random-Seed", "42");
+    let first = res.0.into_body().into_string().await.unwrap();
+
+    cli.get("/12/random-board")
+        .send()
+        .await
+        .assert_status_is_ok();
+    let res = cli.get("/12/random-board").query("seed", &42).send().await;
+    res.assert_text(&first).await;
+
+    let random = || {
+        cli.get("/12/random-board")
+            .query("fill", &0.5)
+            .query("unfinished", &true)
+            .header("Accept", "application/json")
+    };
+    let res = random().send().await;
+    res.assert_status_is_ok();
+    let seed = res.0.headers()["X-Random-Seed"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let first = res.0.into_body().into_string().await.unwrap();
+    let board: serde_json::Value = serde_json::from_str(&first).unwrap();
+    assert_eq!(board["finished"], false);
+    assert!(board["moves"].as_u64().unwrap() <= 8);
+
+    // The position has a history, and the seed gives it back.
+    cli.get("/12/export").send().await.assert_status_is_ok();
+    random()
+        .query("seed", &seed)
+        .send()
+        .await
+        .assert_text(&first)
+        .await;
+
+    cli.get("/12/random-board")
+        .query("fill", &1.5)
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,251 @@
+mod helper;
+use helper::main_router;
+use poem::http::StatusCode;
+use poem::test::{TestClient, TestForm, TestResponse};
+
+const LOCKFILE: &str = r#"
+version = 3
+
+[[package]]
+name = "bitflags"
+version = "1.3.2"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "bef38d45163c2f1dde094a7dfd33ccf595c92905c8f8f4fdc18d06fb1037718a"
+
+[[package]]
+name = "bitflags"
+version = "2.6.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "b048fb63fd8b5923fc5aa7b340d8e156aec7ec02f0c78fa8a6ddc2613f6f71de"
+
+[[package]]
+name = "reindeer"
+version = "0.3.0"
+source = "git+https://github.com/santa/reindeer?branch=main#4b2a1f0"
+dependencies = [
+ "bitflags 1.3.2",
+]
+
+[[package]]
+name = "sleigh"
+version = "0.1.0"
+dependencies = [
+ "bitflags 2.6.0",
+ "reindeer",
+]
+"#;
+
+async fn post(path: &str, accept: &str, lockfile: &str) -> TestResponse {
+    TestClient::new(main_router())
+        .post(path)
+        .header("Accept", accept)
+        .multipart(TestForm::new().text("lockfile", lockfile))
+        .send()
+        .await
+}
+
+#[tokio::test]
+async fn test_day23_lockfile_graph() {
+    let res = post("/23/lockfile/graph", "application/json", LOCKFILE).await;
+    res.assert_status_is_ok();
+    let graph: serde_json::Value = res.json().await.value().deserialize();
+    assert_eq!(graph["nodes"].as_array().unwrap().len(), 4);
+    assert_eq!(
+        graph["nodes"][2],
+        serde_json::json!({
+            "id": "reindeer 0.3.0",
+            "name": "reindeer",
+            "version": "0.3.0",
+            "source": "git+https://github.com/santa/reindeer?branch=main#4b2a1f0"
+        })
+    );
+    assert_eq!(
+        graph["edges"],
+        serde_json::json!([
+            {"from": "reindeer 0.3.0", "to": "bitflags 1.3.2"},
+            {"from": "sleigh 0.1.0", "to": "bitflags 2.6.0"},
+            {"from": "sleigh 0.1.0", "to": "reindeer 0.3.0"}
+        ])
+    );
+
+    let res = post("/23/lockfile/graph", "text/vnd.graphviz", LOCKFILE).await;
+    res.assert_status_is_ok();
+    res.assert_content_type("text/vnd.graphviz");
+    res.assert_text(
+        r#"digraph lockfile {
+    "bitflags 1.3.2";
+    "bitflags 2.6.0";
+    "reindeer 0.3.0";
+    "sleigh 0.1.0";
+    "reindeer 0.3.0" -> "bitflags 1.3.2";
+    "sleigh 0.1.0" -> "bitflags 2.6.0";
+    "sleigh 0.1.0" -> "reindeer 0.3.0";
+}
+"#,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_day23_lockfile_graph_unlocked_dependency() {
+    let lockfile = r#"
+[[package]]
+name = "sleigh"
+version = "0.1.0"
+dependencies = ["bells"]
+"#;
+    post("/23/lockfile/graph", "application/json", lockfile)
+        .await
+        .assert_status(StatusCode::UNPROCESSABLE_ENTITY);
+    post(
+        "/23/lockfile/graph",
+        "application/json",
+        "[[package]]\nname = 1\n",
+    )
+    .await
+    .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_day23_lockfile_duplicates() {
+    let res = post("/23/lockfile/duplicates", "application/json", LOCKFILE).await;
+    res.assert_status_is_ok();
+    res.assert_json(serde_json::json!([
+        {"name": "bitflags", "versions": ["1.3.2", "2.6.0"]}
+    ]))
+    .await;
+}
+
+#[tokio::test]
+async fn test_day23_lockfile_sources() {
+    let res = post("/23/lockfile/sources", "application/json", LOCKFILE).await;
+    res.assert_status_is_ok();
+    res.assert_json(serde_json::json!([{
+        "name": "reindeer",
+        "version": "0.3.0",
+        "source": "git+https://github.com/santa/reindeer?branch=main#4b2a1f0"
+    }]))
+    .await;
+}
+
+#[tokio::test]
+async fn test_day23_lockfile_diff() {
+    let new = r#"
+[[package]]
+name = "bitflags"
+version = "2.6.0"
+checksum = "0000000000000000000000000000000000000000000000000000000000000000"
+
+[[package]]
+name = "reindeer"
+version = "0.4.0"
+dependencies = ["bitflags"]
+
+[[package]]
+name = "sleigh"
+version = "0.1.0"
+
+[[package]]
+name = "snow"
+version = "1.0.0"
+"#;
+    let client = TestClient::new(main_router());
+    let diff = |accept: &str| {
+        client
+            .post("/23/lockfile/diff")
+            .header("Accept", accept)
+            .multipart(TestForm::new().text("old", LOCKFILE).text("new", new))
+            .send()
+    };
+
+    let res = diff("application/json").await;
+    res.assert_status_is_ok();
+    res.assert_json(serde_json::json!({
+        "added": [{"name": "snow", "version": "1.0.0"}],
+        "removed": [{"name": "bitflags", "version": "1.3.2"}],
+        "changed": [{"name": "reindeer", "from": "0.3.0", "to": "0.4.0"}],
+        "checksums": [{
+            "name": "bitflags",
+            "version": "2.6.0",
+            "from": "b048fb63fd8b5923fc5aa7b340d8e156aec7ec02f0c78fa8a6ddc2613f6f71de",
+            "to": "0000000000000000000000000000000000000000000000000000000000000000"
+        }]
+    }))
+    .await;
+
+    let res = diff("text/html").await;
+    res.assert_status_is_ok();
+    res.assert_text(concat!(
+        r#"<table id="lockfile-diff"><thead><tr>"#,
+        "<th>Package</th><th>Change</th><th>Old</th><th>New</th></tr></thead><tbody>",
+        r#"<tr class="added"><td>snow</td><td>added</td><td></td><td>1.0.0</td></tr>"#,
+        r#"<tr class="removed"><td>bitflags</td><td>removed</td><td>1.3.2</td><td></td></tr>"#,
+        r#"<tr class="changed"><td>reindeer</td><td>changed</td><td>0.3.0</td><td>0.4.0</td></tr>"#,
+        r#"<tr class="checksum"><td>bitflags 2.6.0</td><td>checksum</td>"#,
+        "<td>b048fb63fd8b5923fc5aa7b340d8e156aec7ec02f0c78fa8a6ddc2613f6f71de</td>",
+        "<td>0000000000000000000000000000000000000000000000000000000000000000</td></tr>",
+        "</tbody></table>",
+    ))
+    .await;
+
+    client
+        .post("/23/lockfile/diff")
+        .multipart(TestForm::new().text("old", LOCKFILE))
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_day23_lockfile_multiple_files() {
+    let lockfile = |checksum: &str| {
+        format!("[[package]]\nname = \"a\"\nversion = \"1.0.0\"\nchecksum = \"{checksum}\"\n")
+    };
+    let res = TestClient::new(main_router())
+        .post("/23/lockfile")
+        .multipart(
+            TestForm::new()
+                .text("notes", "not a lockfile")
+                .text("lockfile", lockfile("ff00000102"))
+                .text("lockfile", lockfile("00ff000304")),
+        )
+        .send()
+        .await;
+    res.assert_status_is_ok();
+    res.assert_text(concat!(
+        r#"<div style="background-color:#ff0000;top:1px;left:2px;"></div>"#,
+        "\n",
+        r#"<div style="background-color:#00ff00;top:3px;left:4px;"></div>"#,
+    ))
+    .await;
+
+    TestClient::new(main_router())
+        .post("/23/lockfile")
+        .multipart(TestForm::new().text("file", lockfile("ff00000102")))
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_day23_lockfile_checksum_only() {
+    let res = TestClient::new(main_router())
+        .post("/23/lockfile")
+        .multipart(TestForm::new().text(
+            "lockfile",
+            "[[package]]\nchecksum = \"337789faa0372648a8ac286b2f92a53121fe118f12e29009ac504872a5413cc6\"\n",
+        ))
+        .send()
+        .await;
+    res.assert_status_is_ok();
+    res.assert_text(r#"<div style="background-color:#337789;top:250px;left:160px;"></div>"#)
+        .await;
+
+    post(
+        "/23/lockfile/sources",
+        "application/json",
+        "[[package]]\nchecksum = \"337789faa0\"\n",
+    )
+    .await
+    .assert_status(StatusCode::BAD_REQUEST);
+}
@@ -1,7 +1,7 @@
 mod helper;
 use helper::main_router;
 use poem::http::StatusCode;
-use poem::test::TestClient;
+use poem::test::{TestClient, TestForm, TestFormField};
 
 async fn t(path: &str, content_type: &str, body: &str, status: StatusCode, response: Option<&str>) {
     let res = TestClient::new(main_router())
@@ -479,3 +479,742 @@ async fn test_day5_task4_7() {
     )
     .await
 }
+
+async fn validate(content_type: &str, body: &str) -> serde_json::Value {
+    let res = TestClient::new(main_router())
+        .post("/5/manifest")
+        .query("validate", &true)
+        .content_type(content_type)
+        .body(body.to_string())
+        .send()
+        .await;
+    res.assert_status_is_ok();
+    res.json().await.value().deserialize()
+}
+
+#[tokio::test]
+async fn test_day5_validate_toml() {
+    let report = validate(
+        "application/toml",
+        r#"[package]
+name = "sleigh"
+version = "1.0"
+edition = "2022"
+colour = "red"
+
+[dependencies]
+serde = { version = "^1.x.y", features = ["derive"] }
+rand = { branch = "main" }
+"#,
+    )
+    .await;
+    assert_eq!(
+        report,
+        serde_json::json!({
+            "valid": false,
+            "problems": [
+                {
+                    "kind": "invalid_value",
+                    "path": "package.version",
+                    "message": "invalid semver `1.0`: unexpected end of input while parsing minor version number",
+                    "line": 3,
+                    "column": 11
+                },
+                {
+                    "kind": "invalid_value",
+                    "path": "package.edition",
+                    "message": "`2022` is not one of 2015, 2018, 2021, 2024",
+                    "line": 4,
+                    "column": 11
+                },
+                {
+                    "kind": "unknown_key",
+                    "path": "package.colour",
+                    "message": "unknown key `colour`",
+                    "line": 5,
+                    "column": 1
+                },
+                {
+                    "kind": "invalid_value",
+                    "path": "dependencies.serde.version",
+                    "message": "invalid version requirement `^1.x.y`: unexpected character after wildcard in version req",
+                    "line": 8,
+                    "column": 21
+                },
+                {
+                    "kind": "missing_field",
+                    "path": "dependencies.rand",
+                    "message": "a dependency needs `version`, `path`, `git` or `workspace`",
+                    "line": 9,
+                    "column": 8
+                },
+                {
+                    "kind": "invalid_value",
+                    "path": "dependencies.rand.branch",
+                    "message": "`branch` needs `git`",
+                    "line": 9,
+                    "column": 19
+                }
+            ]
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_day5_validate_yaml() {
+    let report = validate(
+        "application/yaml",
+        r#"package:
+  name: sleigh
+  version: 1.0.0
+  metadata:
+    orders:
+      - item: Toy car
+        quantity: 2
+bin:
+  - path: src/main.rs
+  - name: 12
+profile:
+  release:
+    opt-level: 4
+"#,
+    )
+    .await;
+    assert_eq!(
+        report,
+        serde_json::json!({
+            "valid": false,
+            "problems": [
+                {
+                    "kind": "missing_field",
+                    "path": "bin[0]",
+                    "message": "missing `name`",
+                    "line": 9,
+                    "column": 5
+                },
+                {
+                    "kind": "invalid_type",
+                    "path": "bin[1].name",
+                    "message": "expected a string, found an integer",
+                    "line": 10,
+                    "column": 11
+                },
+                {
+                    "kind": "invalid_value",
+                    "path": "profile.release.opt-level",
+                    "message": "expected 0, 1, 2, 3, \"s\" or \"z\", found 4",
+                    "line": 13,
+                    "column": 16
+                }
+            ]
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_day5_validate_syntax_error() {
+    let report = validate(
+        "application/toml",
+        "[package]\nname = \"sleigh\nversion = 1\n",
+    )
+    .await;
+    assert_eq!(report["valid"], false);
+    let problems = report["problems"].as_array().unwrap();
+    assert_eq!(problems.len(), 1);
+    assert_eq!(problems[0]["kind"], "syntax");
+    assert_eq!(problems[0]["line"], 2);
+}
+
+#[tokio::test]
+async fn test_day5_validate_valid() {
+    let report = validate(
+        "application/json",
+        r#"{
+  "package": {
+    "name": "sleigh",
+    "version": "0.1.0",
+    "edition": "2021",
+    "keywords": ["Christmas 2024"]
+  },
+  "dependencies": {
+    "serde": "1",
+    "tokio": { "version": "1.41", "features": ["full"] },
+    "helper": { "path": "../helper" }
+  }
+}"#,
+    )
+    .await;
+    assert_eq!(report, serde_json::json!({ "valid": true, "problems": [] }));
+}
+
+async fn convert(content_type: &str, accept: &str, body: &str) -> poem::test::TestResponse {
+    TestClient::new(main_router())
+        .post("/5/convert")
+        .content_type(content_type)
+        .header("Accept", accept)
+        .body(body.to_string())
+        .send()
+        .await
+}
+
+const CONVERT_TOML: &str = r#"# Santa's sleigh
+[package]
+name = "sleigh"
+version = "0.1.0" # bumped every year
+keywords = ["Christmas 2024"]
+
+[[package.metadata.orders]]
+item = "Toy car"
+quantity = 2
+
+[dependencies]
+serde = "1"
+anyhow = "1"
+"#;
+
+#[tokio::test]
+async fn test_day5_convert_toml_to_json() {
+    let res = convert("application/toml", "application/json", CONVERT_TOML).await;
+    res.assert_status_is_ok();
+    res.assert_content_type("application/json");
+    res.assert_text(
+        r#"{
+  "package": {
+    "name": "sleigh",
+    "version": "0.1.0",
+    "keywords": [
+      "Christmas 2024"
+    ],
+    "metadata": {
+      "orders": [
+        {
+          "item": "Toy car",
+          "quantity": 2
+        }
+      ]
+    }
+  },
+  "dependencies": {
+    "serde": "1",
+    "anyhow": "1"
+  }
+}
+"#,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_day5_convert_yaml_to_toml() {
+    let res = convert(
+        "application/yaml",
+        "text/html, application/toml;q=0.9",
+        r#"package:
+  name: sleigh
+  metadata:
+    orders:
+      - item: Toy car
+        quantity: 2
+  keywords:
+    - Christmas 2024
+"#,
+    )
+    .await;
+    res.assert_status_is_ok();
+    res.assert_content_type("application/toml");
+    res.assert_text(
+        r#"[package]
+name = "sleigh"
+keywords = ["Christmas 2024"]
+
+[[package.metadata.orders]]
+item = "Toy car"
+quantity = 2
+"#,
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_day5_convert_normalizes() {
+    let res = convert("application/toml", "*/*", CONVERT_TOML).await;
+    res.assert_status_is_ok();
+    res.assert_content_type("application/toml");
+    res.assert_text(
+        r#"[package]
+name = "sleigh"
+version = "0.1.0"
+keywords = ["Christmas 2024"]
+
+[[package.metadata.orders]]
+item = "Toy car"
+quantity = 2
+
+[dependencies]
+serde = "1"
+anyhow = "1"
+"#,
+    )
+    .await;
+
+    let res = convert(
+        "application/yaml",
+        "application/yaml",
+        "# Santa's sleigh\npackage:   {name: sleigh,  version: 0.1.0}\n",
+    )
+    .await;
+    res.assert_status_is_ok();
+    res.assert_text("package:\n  name: sleigh\n  version: '0.1.0'\n")
+        .await;
+}
+
+#[tokio::test]
+async fn test_day5_convert_errors() {
+    convert("application/toml", "text/html", CONVERT_TOML)
+        .await
+        .assert_status(StatusCode::NOT_ACCEPTABLE);
+    convert("text/plain", "application/json", CONVERT_TOML)
+        .await
+        .assert_status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    convert("application/toml", "application/json", "[package")
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    convert(
+        "application/json",
+        "application/toml",
+        r#"{"package": null}"#,
+    )
+    .await
+    .assert_status(StatusCode::BAD_REQUEST);
+}
+
+async fn order_report(content_type: &str, body: &str) -> serde_json::Value {
+    let res = TestClient::new(main_router())
+        .post("/5/manifest")
+        .content_type(content_type)
+        .header("Accept", "application/json")
+        .body(body.to_string())
+        .send()
+        .await;
+    res.assert_status_is_ok();
+    res.json().await.value().deserialize()
+}
+
+#[tokio::test]
+async fn test_day5_rejected_orders_toml() {
+    let report = order_report(
+        "application/toml",
+        r#"
+[package]
+name = "coal-in-a-bowl"
+keywords = ["Christmas 2024"]
+
+[[package.metadata.orders]]
+item = "Toy car"
+quantity = 2
+
+[[package.metadata.orders]]
+item = "Coal"
+quantity = "Hahaha get rekt"
+
+[[package.metadata.orders]]
+item = "Lego brick"
+"#,
+    )
+    .await;
+    assert_eq!(
+        report,
+        serde_json::json!({
+            "accepted": true,
+            "rules": [{"name": "magic-keyword", "passed": true, "message": null}],
+            "orders": [{"index": 0, "item": "Toy car", "quantity": 2}],
+            "rejected": [
+                {
+                    "index": 1,
+                    "reason": "invalid type: string \"Hahaha get rekt\", expected u32"
+                },
+                {"index": 2, "reason": "missing field `quantity`"}
+            ],
+            "items": [{"item": "Toy car", "quantity": 2, "unit_price": null, "currency": null, "subtotal": null}],
+            "totals": []
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_day5_rejected_orders_json() {
+    let report = order_report(
+        "application/json",
+        r#"{"package": {"name": "sleigh", "keywords": ["Christmas 2024"], "metadata": {"orders": [
+            {"item": "Toy train", "quantity": 5},
+            {"quantity": 1},
+            {"item": "Horse", "quantity": -2}
+        ]}}}"#,
+    )
+    .await;
+    assert_eq!(
+        report,
+        serde_json::json!({
+            "accepted": true,
+            "rules": [{"name": "magic-keyword", "passed": true, "message": null}],
+            "orders": [{"index": 0, "item": "Toy train", "quantity": 5}],
+            "rejected": [
+                {"index": 1, "reason": "missing field `item`"},
+                {"index": 2, "reason": "invalid value: integer `-2`, expected u32"}
+            ],
+            "items": [{"item": "Toy train", "quantity": 5, "unit_price": null, "currency": null, "subtotal": null}],
+            "totals": []
+        })
+    );
+}
+
+#[tokio::test]
+async fn test_day5_order_totals() {
+    let report = order_report(
+        "application/toml",
+        r#"
+[package]
+name = "sleigh"
+keywords = ["Christmas 2024"]
+
+[[package.metadata.orders]]
+item = "Toy car"
+quantity = 2
+price = 4.99
+currency = "EUR"
+
+[[package.metadata.orders]]
+item = "Lego brick"
+quantity = 230
+price = 0.1
+currency = "EUR"
+
+[[package.metadata.orders]]
+item = "Toy car"
+quantity = 1
+price = 4.99
+currency = "EUR"
+
+[[package.metadata.orders]]
+item = "Toy car"
+quantity = 1
+price = 3.5
+currency = "EUR"
+
+[[package.metadata.orders]]
+item = "Horse"
+quantity = 1
+price = 150
+currency = "USD"
+
+[[package.metadata.orders]]
+item = "Coal"
+quantity = 3
+"#,
+    )
+    .await;
+    assert_eq!(
+        report["items"],
+        serde_json::json!([
+            {"item": "Toy car", "quantity": 3, "unit_price": "4.99", "currency": "EUR", "subtotal": "14.97"},
+            {"item": "Lego brick", "quantity": 230, "unit_price": "0.10", "currency": "EUR", "subtotal": "23.00"},
+            {"item": "Horse", "quantity": 1, "unit_price": "150.00", "currency": "USD", "subtotal": "150.00"},
+            {"item": "Coal", "quantity": 3, "unit_price": null, "currency": null, "subtotal": null}
+        ])
+    );
+    assert_eq!(
+        report["totals"],
+        serde_json::json!([
+            {"currency": "EUR", "amount": "37.97"},
+            {"currency": "USD", "amount": "150.00"}
+        ])
+    );
+    assert_eq!(
+        report["rejected"],
+        serde_json::json!([
+            {"index": 3, "reason": "the price differs from an earlier order of `Toy car`"}
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_day5_workspace() {
+    let form = TestForm::new()
+        .field(
+            TestFormField::text(
+                r#"
+[workspace]
+members = ["crates/*", "tools/cli"]
+exclude = ["crates/scratch"]
+
+[workspace.package]
+version = "0.1.0"
+keywords = ["Christmas 2024"]
+
+[workspace.dependencies]
+serde = { version = "1", features = ["derive"] }
+"#,
+            )
+            .name("manifest")
+            .filename("Cargo.toml"),
+        )
+        .text(
+            "crates/sleigh/Cargo.toml",
+            r#"
+[package]
+name = "sleigh"
+version.workspace = true
+keywords.workspace = true
+
+[dependencies]
+serde = { workspace = true, features = ["rc"] }
+
+[[package.metadata.orders]]
+item = "Toy car"
+quantity = 2
+"#,
+        )
+        .text(
+            "crates/coal/Cargo.toml",
+            r#"
+[package]
+name = "coal"
+keywords = ["Not Christmas"]
+
+[[package.metadata.orders]]
+item = "Coal"
+quantity = 9
+"#,
+        )
+        .text(
+            "crates/elf/Cargo.toml",
+            r#"
+[package]
+name = "elf"
+edition.workspace = true
+"#,
+        )
+        .text(
+            "crates/scratch/Cargo.toml",
+            "[package]\nname = \"scratch\"\n",
+        );
+    let res = TestClient::new(main_router())
+        .post("/5/manifest")
+        .multipart(form)
+        .send()
+        .await;
+    res.assert_status_is_ok();
+    let report: serde_json::Value = res.json().await.value().deserialize();
+    assert_eq!(
+        report,
+        serde_json::json!({"members": [
+            {
+                "path": "crates/sleigh",
+                "name": "sleigh",
+                "report": {
+                    "accepted": true,
+                    "rules": [{"name": "magic-keyword", "passed": true, "message": null}],
+                    "orders": [{"index": 0, "item": "Toy car", "quantity": 2}],
+                    "rejected": [],
+                    "items": [{"item": "Toy car", "quantity": 2, "unit_price": null, "currency": null, "subtotal": null}],
+                    "totals": []
+                },
+                "error": null
+            },
+            {
+                "path": "crates/coal",
+                "name": "coal",
+                "report": {
+                    "accepted": false,
+                    "rules": [{"name": "magic-keyword", "passed": false, "message": "Magic keyword not provided"}],
+                    "orders": [{"index": 0, "item": "Coal", "quantity": 9}],
+                    "rejected": [],
+                    "items": [{"item": "Coal", "quantity": 9, "unit_price": null, "currency": null, "subtotal": null}],
+                    "totals": []
+                },
+                "error": null
+            },
+            {
+                "path": "crates/elf",
+                "name": null,
+                "report": null,
+                "error": "`package.edition` is not in `workspace.package`"
+            },
+            {
+                "path": "crates/scratch",
+                "name": null,
+                "report": null,
+                "error": "not a member of the workspace"
+            },
+            {
+                "path": "tools/cli",
+                "name": null,
+                "report": null,
+                "error": "missing from the upload"
+            }
+        ]})
+    );
+}
+
+#[tokio::test]
+async fn test_day5_workspace_root_package() {
+    let form = TestForm::new().text(
+        "Cargo.toml",
+        r#"
+[package]
+name = "north-pole"
+keywords = ["Christmas 2024"]
+
+[workspace]
+"#,
+    );
+    let res = TestClient::new(main_router())
+        .post("/5/manifest")
+        .multipart(form)
+        .send()
+        .await;
+    res.assert_status_is_ok();
+    let report: serde_json::Value = res.json().await.value().deserialize();
+    assert_eq!(report["members"][0]["path"], ".");
+    assert_eq!(report["members"][0]["name"], "north-pole");
+    assert_eq!(report["members"][0]["report"]["accepted"], true);
+
+    let form = TestForm::new().text("Cargo.toml", "[package]\nname = \"alone\"\n");
+    TestClient::new(main_router())
+        .post("/5/manifest")
+        .multipart(form)
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_day5_workspace_file_parts() {
+    // Like `curl -F 'crates/a/Cargo.toml=@crates/a/Cargo.toml'`, which sends the file name
+    // without its directory.
+    let file = |path: &str, text: &str| {
+        TestFormField::text(text.to_string())
+            .name(path)
+            .filename("Cargo.toml")
+    };
+    let root = "[workspace]\nmembers = [\"crates/*\"]\n";
+    let member = |name: &str| format!("[package]\nname = \"{name}\"\n");
+    let form = TestForm::new()
+        .field(file("Cargo.toml", root))
+        .field(file("crates/a/Cargo.toml", &member("a")))
+        .field(file("crates/b/Cargo.toml", &member("b")));
+    let res = TestClient::new(main_router())
+        .post("/5/manifest")
+        .multipart(form)
+        .send()
+        .await;
+    res.assert_status_is_ok();
+    let report: serde_json::Value = res.json().await.value().deserialize();
+    let members = report["members"].as_array().unwrap();
+    let paths: Vec<&str> = members
+        .iter()
+        .map(|m| m["path"].as_str().unwrap())
+        .collect();
+    assert_eq!(paths, ["crates/a", "crates/b"]);
+
+    let form = TestForm::new()
+        .field(file("Cargo.toml", root))
+        .field(file("Cargo.toml", root));
+    let res = TestClient::new(main_router())
+        .post("/5/manifest")
+        .multipart(form)
+        .send()
+        .await;
+    res.assert_status(StatusCode::BAD_REQUEST);
+    res.assert_text("More than one Cargo.toml for the workspace root")
+        .await;
+}
+
+#[tokio::test]
+async fn test_day5_rules_in_report() {
+    let res = TestClient::new(main_router())
+        .post("/5/manifest")
+        .content_type("application/toml")
+        .header("Accept", "application/json")
+        .body("[package]\nname = \"sleigh\"\nkeywords = [\"Easter\"]\n")
+        .send()
+        .await;
+    res.assert_status(StatusCode::BAD_REQUEST);
+    let report: serde_json::Value = res.json().await.value().deserialize();
+    assert_eq!(report["accepted"], false);
+    assert_eq!(
+        report["rules"],
+        serde_json::json!([
+            {"name": "magic-keyword", "passed": false, "message": "Magic keyword not provided"}
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_day5_rejected_order_with_wrong_item_type() {
+    let manifest = r#"{"package": {"name": "sleigh", "keywords": ["Christmas 2024"], "metadata": {"orders": [
+        {"item": 1, "quantity": 2},
+        {"item": "ok", "quantity": 3}
+    ]}}}"#;
+    t(
+        "/5/manifest",
+        "application/json",
+        manifest,
+        StatusCode::OK,
+        Some("ok: 3"),
+    )
+    .await;
+    let report = order_report("application/json", manifest).await;
+    assert_eq!(
+        report["rejected"],
+        serde_json::json!([
+            {"index": 0, "reason": "invalid type: integer `1`, expected a string"}
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_day5_unpriced_order_still_listed() {
+    let manifest = r#"
+[package]
+name = "sleigh"
+keywords = ["Christmas 2024"]
+
+[[package.metadata.orders]]
+item = "Gift"
+quantity = 1
+price = "free"
+
+[[package.metadata.orders]]
+item = "Bell"
+quantity = 2
+price = "1.5"
+currency = "EUR"
+
+[[package.metadata.orders]]
+item = "Star"
+quantity = 1
+price = 0.001
+"#;
+    t(
+        "/5/manifest",
+        "application/toml",
+        manifest,
+        StatusCode::OK,
+        Some("Gift: 1\nBell: 2\nStar: 1"),
+    )
+    .await;
+    let report = order_report("application/toml", manifest).await;
+    let reason = "`price` must be a number of at least 0 with at most 2 decimals";
+    assert_eq!(
+        report["rejected"],
+        serde_json::json!([
+            {"index": 0, "reason": reason},
+            {"index": 2, "reason": reason}
+        ])
+    );
+    assert_eq!(
+        report["totals"],
+        serde_json::json!([{"currency": "EUR", "amount": "3.00"}])
+    );
+}
@@ -0,0 +1,174 @@
+mod helper;
+use helper::main_router;
+use poem::http::StatusCode;
+use poem::test::TestClient;
+
+#[tokio::test]
+async fn test_day9_task1_1() {
+    let cli = TestClient::new(main_router());
+    for _ in 0..5 {
+        let res = cli.post("/9/milk").send().await;
+        res.assert_status_is_ok();
+        res.assert_text("Milk withdrawn\n").await;
+    }
+    let res = cli.post("/9/milk").send().await;
+    res.assert_status(StatusCode::TOO_MANY_REQUESTS);
+    res.assert_text("No milk available\n").await;
+}
+
+#[tokio::test]
+async fn test_day9_task2_1() {
+    let res = TestClient::new(main_router())
+        .post("/9/milk")
+        .content_type("application/json")
+        .body(r#"{"liters":5}"#)
+        .send()
+        .await;
+    res.assert_status_is_ok();
+    res.assert_json(serde_json::json!({"gallons": 1.320_864_5}))
+        .await;
+}
+
+#[tokio::test]
+async fn test_day9_task4_refill() {
+    let cli = TestClient::new(main_router());
+    for _ in 0..5 {
+        cli.post("/9/milk").send().await.assert_status_is_ok();
+    }
+    cli.post("/9/refill").send().await.assert_status_is_ok();
+    for _ in 0..5 {
+        cli.post("/9/milk").send().await.assert_status_is_ok();
+    }
+    cli.post("/9/milk")
+        .send()
+        .await
+        .assert_status(StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn test_day9_partial_refill() {
+    let cli = TestClient::new(main_router());
+    for _ in 0..5 {
+        cli.post("/9/milk").send().await.assert_status_is_ok();
+    }
+    cli.post("/9/refill")
+        .query("liters", &2)
+        .send()
+        .await
+        .assert_status_is_ok();
+    for _ in 0..2 {
+        cli.post("/9/milk").send().await.assert_status_is_ok();
+    }
+    cli.post("/9/milk")
+        .send()
+        .await
+        .assert_status(StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn test_day9_capacity() {
+    let cli = TestClient::new(main_router());
+    cli.post("/9/capacity")
+        .send()
+        .await
+        .assert_status(StatusCode::BAD_REQUEST);
+    cli.post("/9/capacity")
+        .query("liters", &8)
+        .send()
+        .await
+        .assert_status_is_ok();
+    cli.post("/9/refill").send().await.assert_status_is_ok();
+    for _ in 0..8 {
+        cli.post("/9/milk").send().await.assert_status_is_ok();
+    }
+    cli.post("/9/milk")
+        .send()
+        .await
+        .assert_status(StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn test_day9_units() {
+    let res = TestClient::new(main_router())
+        .post("/9/milk")
+        .content_type("application/json")
+        .body(r#"{"from":"gallons","to":"ml","value":3}"#)
+        .send()
+        .await;
+    res.assert_status_is_ok();
+    let json = res.json().await;
+    let json = json.value().object();
+    json.get("from").assert_string("gallons");
+    json.get("to").assert_string("ml");
+    assert!((json.get("result").f64() - 11_356.235_352).abs() < 1e-6);
+}
+
+#[tokio::test]
+async fn test_day9_units_incompatible() {
+    let res = TestClient::new(main_router())
+        .post("/9/milk")
+        .content_type("application/json")
+        .body(r#"{"from":"kg","to":"liters","value":1}"#)
+        .send()
+        .await;
+    res.assert_status(StatusCode::BAD_REQUEST);
+    res.assert_text("Cannot convert mass to volume\n").await;
+}
+
+#[tokio::test]
+async fn test_day9_batch() {
+    let cli = TestClient::new(main_router());
+    let res = cli
+        .post("/9/milk")
+        .content_type("application/json")
+        .body(r#"[{"liters":5},{"from":"kg","to":"g","value":2},{"from":"kg","to":"ml","value":2},{"pints":1,"litres":1}]"#)
+        .send()
+        .await;
+    res.assert_status_is_ok();
+    res.assert_json(serde_json::json!([
+        {"gallons": 1.320_864_5},
+        {"from": "kg", "to": "g", "value": 2.0, "result": 2000.0},
+        {"error": "Cannot convert mass to volume"},
+        {"error": "Expected exactly one of liters, gallons, litres or pints"},
+    ]))
+    .await;
+
+    // The batch above cost a single liter.
+    for _ in 0..4 {
+        cli.post("/9/milk").send().await.assert_status_is_ok();
+    }
+    cli.post("/9/milk")
+        .send()
+        .await
+        .assert_status(StatusCode::TOO_MANY_REQUESTS);
+}
+
+#[tokio::test]
+async fn test_day9_batch_cost() {
+    let cli = TestClient::new(main_router());
+    let batch = |n: usize| format!("[{}]", vec![r#"{"liters":1}"#; n].join(","));
+    cli.post("/9/milk")
+        .content_type("application/json")
+        .body(batch(501))
+        .send()
+        .await
+        .assert_status(StatusCode::PAYLOAD_TOO_LARGE);
+    cli.post("/9/milk")
+        .content_type("application/json")
+        .body(batch(201))
+        .send()
+        .await
+        .assert_status_is_ok();
+    cli.post("/9/milk")
+        .content_type("application/json")
+        .body(batch(300))
+        .send()
+        .await
+        .assert_status(StatusCode::TOO_MANY_REQUESTS);
+    cli.post("/9/milk")
+        .content_type("application/json")
+        .body(batch(200))
+        .send()
+        .await
+        .assert_status_is_ok();
+}